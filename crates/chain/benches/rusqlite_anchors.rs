@@ -0,0 +1,147 @@
+use bdk_chain::rusqlite::{self, named_params};
+use bdk_chain::{tx_graph, BlockId, ConfirmationBlockTime, Impl};
+use bitcoin::{hashes::Hash, BlockHash, Txid};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+
+const ANCHOR_COUNT: u32 = 100_000;
+/// Table mimicking the v0 schema, where anchors were stored as JSON.
+const JSON_ANCHORS_TABLE_NAME: &str = "bench_json_anchors";
+
+fn anchors() -> impl Iterator<Item = (ConfirmationBlockTime, Txid)> {
+    (0..ANCHOR_COUNT).map(|i| {
+        let anchor = ConfirmationBlockTime {
+            block_id: BlockId {
+                height: i,
+                hash: BlockHash::hash(&i.to_le_bytes()),
+            },
+            confirmation_time: 1_231_006_505 + u64::from(i),
+        };
+        (anchor, Txid::hash(&i.to_be_bytes()))
+    })
+}
+
+fn setup() -> rusqlite::Connection {
+    let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+    let db_tx = conn.transaction().unwrap();
+    ChangeSet::init_sqlite_tables(&db_tx).unwrap();
+    ChangeSet {
+        anchors: anchors().collect(),
+        ..Default::default()
+    }
+    .persist_to_sqlite(&db_tx)
+    .unwrap();
+
+    db_tx
+        .execute(
+            &format!(
+                "CREATE TABLE {} ( txid TEXT NOT NULL, anchor TEXT NOT NULL ) STRICT",
+                JSON_ANCHORS_TABLE_NAME
+            ),
+            (),
+        )
+        .unwrap();
+    let mut statement = db_tx
+        .prepare(&format!(
+            "INSERT INTO {}(txid, anchor) VALUES(:txid, :anchor)",
+            JSON_ANCHORS_TABLE_NAME
+        ))
+        .unwrap();
+    for (anchor, txid) in anchors() {
+        statement
+            .execute(named_params! {
+                ":txid": Impl(txid),
+                ":anchor": serde_json::to_string(&anchor).unwrap(),
+            })
+            .unwrap();
+    }
+    drop(statement);
+    db_tx.commit().unwrap();
+    conn
+}
+
+/// The anchor query of `ChangeSet::from_sqlite`, without loading the other tables.
+fn load_column_anchors(db_tx: &rusqlite::Transaction) -> ChangeSet {
+    let mut changeset = ChangeSet::default();
+    let mut statement = db_tx
+        .prepare(&format!(
+            "SELECT block_hash, block_height, confirmation_time, txid FROM {}",
+            ChangeSet::ANCHORS_TABLE_NAME,
+        ))
+        .unwrap();
+    let row_iter = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, Impl<BlockHash>>("block_hash")?,
+                row.get::<_, u32>("block_height")?,
+                row.get::<_, u64>("confirmation_time")?,
+                row.get::<_, Impl<Txid>>("txid")?,
+            ))
+        })
+        .unwrap();
+    for row in row_iter {
+        let (Impl(hash), height, confirmation_time, Impl(txid)) = row.unwrap();
+        let anchor = ConfirmationBlockTime {
+            block_id: BlockId { height, hash },
+            confirmation_time,
+        };
+        changeset.anchors.insert((anchor, txid));
+    }
+    changeset
+}
+
+fn load_json_anchors(db_tx: &rusqlite::Transaction) -> ChangeSet {
+    let mut changeset = ChangeSet::default();
+    let mut statement = db_tx
+        .prepare(&format!(
+            "SELECT txid, anchor FROM {}",
+            JSON_ANCHORS_TABLE_NAME
+        ))
+        .unwrap();
+    let row_iter = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, Impl<Txid>>("txid")?,
+                row.get::<_, String>("anchor")?,
+            ))
+        })
+        .unwrap();
+    for row in row_iter {
+        let (Impl(txid), anchor) = row.unwrap();
+        let anchor: ConfirmationBlockTime = serde_json::from_str(&anchor).unwrap();
+        changeset.anchors.insert((anchor, txid));
+    }
+    changeset
+}
+
+pub fn load_anchors(c: &mut Criterion) {
+    let conn = std::sync::Arc::new(std::sync::Mutex::new(setup()));
+    c.bench_function("load_anchors::columns", {
+        let conn = conn.clone();
+        move |b| {
+            b.iter(|| {
+                let mut conn = conn.lock().unwrap();
+                let db_tx = conn.transaction().unwrap();
+                black_box(load_column_anchors(&db_tx))
+            })
+        }
+    });
+    c.bench_function("load_anchors::json", {
+        let conn = conn.clone();
+        move |b| {
+            b.iter(|| {
+                let mut conn = conn.lock().unwrap();
+                let db_tx = conn.transaction().unwrap();
+                black_box(load_json_anchors(&db_tx))
+            })
+        }
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = load_anchors
+}
+criterion_main!(benches);
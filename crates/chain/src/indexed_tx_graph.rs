@@ -7,7 +7,7 @@ use bitcoin::{Block, OutPoint, Transaction, TxOut, Txid};
 
 use crate::{
     tx_graph::{self, TxGraph},
-    Anchor, BlockId, Indexer, Merge, TxPosInBlock,
+    Anchor, BlockId, EstimateSize, Indexer, Merge, TxPosInBlock,
 };
 
 /// The [`IndexedTxGraph`] combines a [`TxGraph`] and an [`Indexer`] implementation.
@@ -370,6 +370,12 @@ impl<A: Anchor, IA: Merge> Merge for ChangeSet<A, IA> {
     }
 }
 
+impl<A, IA: EstimateSize> EstimateSize for ChangeSet<A, IA> {
+    fn estimated_size(&self) -> usize {
+        self.tx_graph.estimated_size() + self.indexer.estimated_size()
+    }
+}
+
 impl<A, IA: Default> From<tx_graph::ChangeSet<A>> for ChangeSet<A, IA> {
     fn from(graph: tx_graph::ChangeSet<A>) -> Self {
         Self {
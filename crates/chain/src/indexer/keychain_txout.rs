@@ -16,7 +16,7 @@ use core::{
     ops::{Bound, RangeBounds},
 };
 
-use crate::Merge;
+use crate::{EstimateSize, Merge};
 
 /// The default lookahead for a [`KeychainTxOutIndex`]
 pub const DEFAULT_LOOKAHEAD: u32 = 25;
@@ -869,6 +869,12 @@ impl Merge for ChangeSet {
     }
 }
 
+impl EstimateSize for ChangeSet {
+    fn estimated_size(&self) -> usize {
+        self.last_revealed.estimated_size()
+    }
+}
+
 /// Trait to extend [`SyncRequestBuilder`].
 pub trait SyncRequestBuilderExt<K> {
     /// Add [`Script`](bitcoin::Script)s that are revealed by the `indexer` of the given `spk_range`
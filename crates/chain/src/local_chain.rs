@@ -4,7 +4,7 @@ use core::convert::Infallible;
 use core::ops::RangeBounds;
 
 use crate::collections::BTreeMap;
-use crate::{BlockId, ChainOracle, Merge};
+use crate::{BlockId, ChainOracle, EstimateSize, Merge};
 pub use bdk_core::{CheckPoint, CheckPointIter};
 use bitcoin::block::Header;
 use bitcoin::BlockHash;
@@ -425,6 +425,12 @@ impl Merge for ChangeSet {
     }
 }
 
+impl EstimateSize for ChangeSet {
+    fn estimated_size(&self) -> usize {
+        self.blocks.estimated_size()
+    }
+}
+
 impl<B: IntoIterator<Item = (u32, Option<BlockHash>)>> From<B> for ChangeSet {
     fn from(blocks: B) -> Self {
         Self {
@@ -1,4 +1,14 @@
 //! Support for persisting `bdk_chain` structures to SQLite using [`rusqlite`].
+//!
+//! Loaders never depend on the order in which SQLite returns rows. Changesets are built from
+//! `BTreeMap`s and `BTreeSet`s, and loaders that return a `Vec` sort it with `ORDER BY` on the
+//! key columns. Note that txids are ordered by their stored hex text, which is not the order of
+//! [`Txid`](bitcoin::Txid)'s `Ord` implementation.
+//!
+//! Loaders also never `SELECT *`: they name the columns they read, and read them by name (see
+//! [`get_sql`]). A database written by a newer version that added optional columns therefore
+//! stays loadable by an older version, which ignores the columns it doesn't know. Custom loaders
+//! should do the same to stay forward compatible.
 
 use crate::*;
 use core::str::FromStr;
@@ -17,13 +27,95 @@ use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput,
 use rusqlite::OptionalExtension;
 use rusqlite::Transaction;
 
+mod connection;
+pub use connection::*;
+pub mod kv;
+pub mod labels;
+pub mod mempool_snapshot;
+pub mod replacements;
+#[cfg(feature = "miniscript")]
+pub mod revealed_ranges;
+mod serde_blob;
+pub use serde_blob::*;
+mod sql_type;
+pub mod utxos;
+pub use sql_type::*;
+mod timestamps;
+pub use timestamps::*;
+#[cfg(feature = "normalized-tx")]
+pub mod normalized_tx;
+pub mod tx_meta;
+
 /// Table name for schemas.
 pub const SCHEMAS_TABLE_NAME: &str = "bdk_schemas";
 
+/// Statement that creates a schemas table named `table_name`.
+fn create_schemas_table_sql(table_name: &str) -> String {
+    format!("CREATE TABLE IF NOT EXISTS {}( name TEXT PRIMARY KEY NOT NULL, version INTEGER NOT NULL ) STRICT", table_name)
+}
+
 /// Initialize the schema table.
 fn init_schemas_table(db_tx: &Transaction) -> rusqlite::Result<()> {
-    let sql = format!("CREATE TABLE IF NOT EXISTS {}( name TEXT PRIMARY KEY NOT NULL, version INTEGER NOT NULL ) STRICT", SCHEMAS_TABLE_NAME);
-    db_tx.execute(&sql, ())?;
+    db_tx.execute(&create_schemas_table_sql(SCHEMAS_TABLE_NAME), ())?;
+    Ok(())
+}
+
+/// Name of the `TEMP` table that records the migration scripts of each schema name.
+///
+/// Temporary tables are private to the connection, so this is never written to the database file.
+const SCHEMA_SCRIPTS_TABLE_NAME: &str = "bdk_schema_scripts";
+
+/// Record the `versioned_scripts` of `schema_name` in [`SCHEMA_SCRIPTS_TABLE_NAME`].
+///
+/// Fails if `schema_name` was already migrated on this connection with a different script for
+/// one of the versions, which means that two components use the same schema name and would
+/// share its version counter. Migrating the same schema again with newer versions appended is
+/// allowed.
+fn register_schema_scripts(
+    db_tx: &Transaction,
+    schema_name: &str,
+    versioned_scripts: &[&str],
+) -> rusqlite::Result<()> {
+    db_tx.execute(
+        &format!(
+            "CREATE TEMP TABLE IF NOT EXISTS {}( name TEXT NOT NULL, version INTEGER NOT NULL, script TEXT NOT NULL, PRIMARY KEY (name, version) ) STRICT",
+            SCHEMA_SCRIPTS_TABLE_NAME,
+        ),
+        (),
+    )?;
+    let mut select_statement = db_tx.prepare_cached(&format!(
+        "SELECT script FROM temp.{} WHERE name=:name AND version=:version",
+        SCHEMA_SCRIPTS_TABLE_NAME,
+    ))?;
+    let mut insert_statement = db_tx.prepare_cached(&format!(
+        "INSERT INTO temp.{}(name, version, script) VALUES(:name, :version, :script)",
+        SCHEMA_SCRIPTS_TABLE_NAME,
+    ))?;
+    for (version, &script) in versioned_scripts.iter().enumerate() {
+        let params = named_params! { ":name": schema_name, ":version": version as u32 };
+        let registered = select_statement
+            .query_row(params, |row| row.get::<_, String>("script"))
+            .optional()?;
+        match registered {
+            Some(registered) if registered != script => {
+                return Err(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+                    Some(format!(
+                        "schema name {} is already migrated with a different script for v{}",
+                        schema_name, version
+                    )),
+                ))
+            }
+            Some(_) => {}
+            None => {
+                insert_statement.execute(named_params! {
+                    ":name": schema_name,
+                    ":version": version as u32,
+                    ":script": script,
+                })?;
+            }
+        }
+    }
     Ok(())
 }
 
@@ -57,28 +149,744 @@ fn set_schema_version(
     Ok(())
 }
 
+/// Returns whether a table named `table_name` exists.
+fn table_exists(db_tx: &Transaction, table_name: &str) -> rusqlite::Result<bool> {
+    db_tx
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type='table' AND name=:name",
+            named_params! { ":name": table_name },
+            |_| Ok(()),
+        )
+        .optional()
+        .map(|row| row.is_some())
+}
+
+/// Returns the version that `schema_name` is migrated to in the database.
+///
+/// Returns `None` if `schema_name` (or the schemas table itself) has not been initialized.
+/// Comparing this with the latest version known to the running code (e.g. the `SCHEMA_VERSION`
+/// of a changeset) tells whether the database was written by a newer release.
+pub fn stored_schema_version(
+    db_tx: &Transaction,
+    schema_name: &str,
+) -> rusqlite::Result<Option<u32>> {
+    if !table_exists(db_tx, SCHEMAS_TABLE_NAME)? {
+        return Ok(None);
+    }
+    schema_version(db_tx, schema_name)
+}
+
+/// Returns whether `schema_name` has been migrated to at least `min_version`.
+///
+/// This can be used to gate features on migrations that have been applied. Returns `false` if
+/// `schema_name` (or the schemas table itself) has not been initialized.
+pub fn schema_at_least(
+    db_tx: &Transaction,
+    schema_name: &str,
+    min_version: u32,
+) -> rusqlite::Result<bool> {
+    Ok(stored_schema_version(db_tx, schema_name)?.map_or(false, |version| version >= min_version))
+}
+
+/// Check that the schemas table is well-formed.
+///
+/// Version lookups assume that the table is `STRICT` with a `name TEXT` primary key and a
+/// `version INTEGER NOT NULL` column, so that each schema has exactly one non-negative version.
+/// Tables created by other tools (or by hand) may violate this, in which case an error describing
+/// every problem found is returned. A missing schemas table is fine. A malformed table can be
+/// fixed with [`normalize_schemas_table`].
+pub fn verify_schemas_table(db_tx: &Transaction) -> rusqlite::Result<()> {
+    if !table_exists(db_tx, SCHEMAS_TABLE_NAME)? {
+        return Ok(());
+    }
+    let mut problems = Vec::<String>::new();
+
+    let strict = db_tx.query_row(
+        "SELECT strict FROM pragma_table_list WHERE schema='main' AND name=:name",
+        named_params! { ":name": SCHEMAS_TABLE_NAME },
+        |row| row.get::<_, bool>(0),
+    )?;
+    if !strict {
+        problems.push("table is not STRICT".into());
+    }
+    let columns = db_tx
+        .prepare(&format!(
+            "SELECT name, type, \"notnull\", pk FROM pragma_table_info('{}') ORDER BY cid",
+            SCHEMAS_TABLE_NAME,
+        ))?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, bool>(2)?,
+                row.get::<_, u32>(3)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let expected_columns = [
+        ("name".to_string(), "TEXT".to_string(), true, 1),
+        ("version".to_string(), "INTEGER".to_string(), true, 0),
+    ];
+    if columns != expected_columns {
+        problems.push(format!(
+            "unexpected columns {:?}",
+            columns
+                .iter()
+                .map(|(name, ty, _, _)| format!("{} {}", name, ty))
+                .collect::<Vec<_>>()
+        ));
+    }
+
+    let duplicates = db_tx
+        .prepare(&format!(
+            "SELECT name FROM {} GROUP BY name HAVING COUNT(*) > 1",
+            SCHEMAS_TABLE_NAME,
+        ))?
+        .query_map([], |row| row.get::<_, Option<String>>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if !duplicates.is_empty() {
+        problems.push(format!("duplicate schema names {:?}", duplicates));
+    }
+    let invalid_versions = db_tx
+        .prepare(&format!(
+            "SELECT name FROM {} WHERE typeof(version) != 'integer' OR version < 0",
+            SCHEMAS_TABLE_NAME,
+        ))?
+        .query_map([], |row| row.get::<_, Option<String>>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if !invalid_versions.is_empty() {
+        problems.push(format!(
+            "missing or invalid versions of {:?}",
+            invalid_versions
+        ));
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CORRUPT),
+        Some(format!(
+            "malformed {} table: {}",
+            SCHEMAS_TABLE_NAME,
+            problems.join("; ")
+        )),
+    ))
+}
+
+/// Rebuild the schemas table so that it passes [`verify_schemas_table`].
+///
+/// The table is recreated with the expected `STRICT` definition. Each schema name keeps the
+/// greatest valid version recorded for it. Rows without a name or without a valid version are
+/// dropped, so their schemas count as not initialized. Do this within the same `db_tx` as
+/// the subsequent migrations.
+pub fn normalize_schemas_table(db_tx: &Transaction) -> rusqlite::Result<()> {
+    if !table_exists(db_tx, SCHEMAS_TABLE_NAME)? {
+        return Ok(());
+    }
+    let temp_table_name = format!("{}_normalized", SCHEMAS_TABLE_NAME);
+    db_tx.execute_batch(&format!(
+        "{create}; \
+        INSERT INTO {temp}(name, version) \
+        SELECT CAST(name AS TEXT), MAX(version) FROM {table} \
+        WHERE name IS NOT NULL AND typeof(version) = 'integer' AND version >= 0 \
+        GROUP BY CAST(name AS TEXT); \
+        DROP TABLE {table}; \
+        ALTER TABLE {temp} RENAME TO {table}",
+        create = create_schemas_table_sql(&temp_table_name),
+        temp = temp_table_name,
+        table = SCHEMAS_TABLE_NAME,
+    ))?;
+    db_tx.flush_prepared_statement_cache();
+    Ok(())
+}
+
 /// Runs logic that initializes/migrates the table schemas.
+///
+/// Scripts may call application-defined SQL functions (e.g. to backfill a derived column). Such
+/// functions must be registered on the connection (with `Connection::create_scalar_function` of
+/// rusqlite's `functions` feature) before the transaction is started. Registration is per
+/// connection, so it has to be repeated for every newly opened connection.
+///
+/// Every schema name must belong to a single component. Migrating a name that was already
+/// migrated on the same connection with different scripts (e.g. by another component's
+/// `init_sqlite_tables` within the same transaction) is an error, since both would share one
+/// version counter.
 pub fn migrate_schema(
     db_tx: &Transaction,
     schema_name: &str,
     versioned_scripts: &[&str],
 ) -> rusqlite::Result<()> {
-    init_schemas_table(db_tx)?;
-    let current_version = schema_version(db_tx, schema_name)?;
-    let exec_from = current_version.map_or(0_usize, |v| v as usize + 1);
-    let scripts_to_exec = versioned_scripts.iter().enumerate().skip(exec_from);
-    for (version, script) in scripts_to_exec {
-        set_schema_version(db_tx, schema_name, version as u32)?;
-        db_tx.execute_batch(script)?;
+    let mut plan = MigrationPlan::default();
+    plan.add_schema(db_tx, schema_name, versioned_scripts)?;
+    plan.execute(db_tx, |_, _| {})
+}
+
+/// Returns an `INSERT ... ON CONFLICT ... DO UPDATE` statement for `table_name`.
+///
+/// Each column is bound to a named parameter of the same name prefixed with `:` (e.g. `:txid`),
+/// to be passed with [`named_params!`]. A row that conflicts on `key_columns` has its
+/// `value_columns` updated instead. Without `value_columns`, conflicting rows are left as they
+/// are (`DO NOTHING`).
+///
+/// Errors if the table name or any column name is not a valid identifier.
+pub fn upsert_sql(
+    table_name: &str,
+    key_columns: &[&str],
+    value_columns: &[&str],
+) -> Result<String, InvalidIdentifier> {
+    validate_identifiers(
+        core::iter::once(table_name)
+            .chain(key_columns.iter().copied())
+            .chain(value_columns.iter().copied()),
+    )?;
+    let columns = key_columns
+        .iter()
+        .chain(value_columns)
+        .copied()
+        .collect::<Vec<_>>();
+    let params = columns
+        .iter()
+        .map(|column| format!(":{}", column))
+        .collect::<Vec<_>>();
+    let action = if value_columns.is_empty() {
+        "NOTHING".to_owned()
+    } else {
+        let assignments = value_columns
+            .iter()
+            .map(|column| format!("{column}=:{column}", column = column))
+            .collect::<Vec<_>>();
+        format!("UPDATE SET {}", assignments.join(", "))
+    };
+    Ok(format!(
+        "INSERT INTO {}({}) VALUES({}) ON CONFLICT({}) DO {}",
+        table_name,
+        columns.join(", "),
+        params.join(", "),
+        key_columns.join(", "),
+        action,
+    ))
+}
+
+/// Checks that no schema name appears twice in `schema_names`.
+///
+/// Components that share a schema name also share a version counter, so one component's
+/// migrations would be skipped (or run against the wrong tables). Composite changesets should
+/// call this with the schema names of all their components before running migrations.
+pub fn check_unique_schema_names(schema_names: &[&str]) -> rusqlite::Result<()> {
+    for (i, schema_name) in schema_names.iter().enumerate() {
+        if schema_names[..i].contains(schema_name) {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+                Some(format!("duplicate schema name: {}", schema_name)),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Returns a migration script that (re)creates the view `view_name` as `select`.
+///
+/// Loaders that query a view instead of the physical table keep working when a later schema
+/// version changes the table layout, as long as that version's script repoints the view so it
+/// exposes the same columns. Append the returned script to each version that changes the layout.
+///
+/// Errors if `view_name` is not a valid identifier. `select` is inserted verbatim, so it must be a
+/// trusted statement rather than caller-supplied input.
+pub fn compat_view_script(view_name: &str, select: &str) -> Result<String, InvalidIdentifier> {
+    validate_identifier(view_name)?;
+    Ok(format!(
+        "DROP VIEW IF EXISTS {view}; CREATE VIEW {view} AS {select}",
+        view = view_name,
+        select = select,
+    ))
+}
+
+/// A single schema version that a [`MigrationPlan`] will apply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStep<'a> {
+    /// Name of the schema being migrated.
+    pub schema_name: &'a str,
+    /// The version that this step migrates the schema to.
+    pub version: u32,
+    /// Number of SQL statements in the script of this version.
+    ///
+    /// This is determined by splitting the script on `;`, so it is only meant for display.
+    pub statement_count: usize,
+    script: &'a str,
+}
+
+/// The pending schema migrations of one or more schemas.
+///
+/// This allows displaying migration progress (e.g. "step 2 of 5"). Schemas are added with
+/// [`add_schema`](Self::add_schema) and the plan is applied with [`execute`](Self::execute).
+/// [`migrate_schema`] is a shorthand for a plan of a single schema without progress reporting.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MigrationPlan<'a> {
+    schema_names: Vec<&'a str>,
+    steps: Vec<MigrationStep<'a>>,
+}
+
+impl<'a> MigrationPlan<'a> {
+    /// Add the versions of `schema_name` that are not yet applied.
+    ///
+    /// Versions are determined from the position of each script in `versioned_scripts`, the same
+    /// way as with [`migrate_schema`]. Adding the same `schema_name` twice to a plan is an error,
+    /// since both would share (and race on) a single version counter. So is adding a name that
+    /// was migrated with different scripts before on the same connection.
+    pub fn add_schema(
+        &mut self,
+        db_tx: &Transaction,
+        schema_name: &'a str,
+        versioned_scripts: &[&'a str],
+    ) -> rusqlite::Result<()> {
+        if self.schema_names.contains(&schema_name) {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+                Some(format!("duplicate schema name: {}", schema_name)),
+            ));
+        }
+        register_schema_scripts(db_tx, schema_name, versioned_scripts)?;
+        let current_version = if table_exists(db_tx, SCHEMAS_TABLE_NAME)? {
+            schema_version(db_tx, schema_name)?
+        } else {
+            None
+        };
+        let exec_from = current_version.map_or(0_usize, |v| v as usize + 1);
+        let steps =
+            versioned_scripts
+                .iter()
+                .enumerate()
+                .skip(exec_from)
+                .map(|(version, &script)| MigrationStep {
+                    schema_name,
+                    version: version as u32,
+                    statement_count: script
+                        .split(';')
+                        .filter(|statement| !statement.trim().is_empty())
+                        .count(),
+                    script,
+                });
+        self.steps.extend(steps);
+        self.schema_names.push(schema_name);
+        Ok(())
+    }
+
+    /// The steps of the plan, in order of execution.
+    pub fn steps(&self) -> &[MigrationStep<'a>] {
+        &self.steps
+    }
+
+    /// Whether there are no pending migrations.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Apply all steps of the plan.
+    ///
+    /// `on_step_completed` is called after each step with the number of completed steps and the
+    /// completed step itself. If any step was applied, the prepared statement cache of the
+    /// connection is flushed so that later statements are prepared against the new schema.
+    ///
+    /// An empty plan succeeds without writing to the database, so checking for migrations within
+    /// a deferred transaction (the default of `Connection::transaction`) does not take a write
+    /// lock when the schemas are up to date. On a read-only connection, a plan with pending steps
+    /// fails with an error naming the schemas and versions that are
+    /// pending (instead of failing on the first write).
+    pub fn execute<F>(self, db_tx: &Transaction, mut on_step_completed: F) -> rusqlite::Result<()>
+    where
+        F: FnMut(usize, &MigrationStep<'a>),
+    {
+        // without pending steps nothing is written, so a deferred transaction never escalates
+        // to a write lock
+        if self.steps.is_empty() {
+            return Ok(());
+        }
+        if db_tx.is_readonly(rusqlite::DatabaseName::Main)? {
+            let pending = self
+                .steps
+                .iter()
+                .map(|step| format!("{} v{}", step.schema_name, step.version))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_READONLY),
+                Some(format!(
+                    "cannot migrate schemas on a read-only connection, pending: {}",
+                    pending
+                )),
+            ));
+        }
+        init_schemas_table(db_tx)?;
+        for (i, step) in self.steps.iter().enumerate() {
+            set_schema_version(db_tx, step.schema_name, step.version)?;
+            db_tx.execute_batch(step.script)?;
+            on_step_completed(i + 1, step);
+        }
+        db_tx.flush_prepared_statement_cache();
+        Ok(())
+    }
+}
+
+/// Returns a `CREATE TABLE` statement for a `WITHOUT ROWID` table keyed by `key_column`.
+///
+/// Tables that are always looked up by a key such as a txid or descriptor id are smaller and
+/// faster without the implicit rowid, since rows are then stored in the primary key's b-tree.
+/// Each of `other_columns` is a full column definition (e.g. `"last_revealed INTEGER NOT NULL"`).
+/// The statement is meant to be used in a versioned migration script.
+///
+/// Errors if the table name, key column or key type is not a valid identifier. `other_columns`
+/// are inserted verbatim, so they must be trusted definitions rather than caller-supplied input.
+pub fn create_keyed_table(
+    table_name: &str,
+    key_column: &str,
+    key_type: &str,
+    other_columns: &[&str],
+) -> Result<String, InvalidIdentifier> {
+    validate_identifiers([table_name, key_column, key_type])?;
+    let mut columns = vec![format!("{} {} PRIMARY KEY NOT NULL", key_column, key_type)];
+    columns.extend(other_columns.iter().map(|column| column.to_string()));
+    Ok(format!(
+        "CREATE TABLE {} ( {} ) WITHOUT ROWID, STRICT",
+        table_name,
+        columns.join(", "),
+    ))
+}
+
+/// Returns a `CREATE TABLE` statement for a `WITHOUT ROWID` table keyed by several columns.
+///
+/// This is [`create_keyed_table`] for tables with a natural composite key, such as anchors keyed
+/// by `(txid, block_hash)`. Each of `key_columns` is a `(name, type)` pair, and the primary key
+/// spans them in the given order. Rows can be written with [`upsert_sql`] using the same key
+/// column names as the conflict target.
+///
+/// Errors the same way as [`create_keyed_table`], for the table name and every key name and type.
+pub fn create_composite_keyed_table(
+    table_name: &str,
+    key_columns: &[(&str, &str)],
+    other_columns: &[&str],
+) -> Result<String, InvalidIdentifier> {
+    validate_identifiers(
+        core::iter::once(table_name).chain(key_columns.iter().flat_map(|(name, ty)| [*name, *ty])),
+    )?;
+    let mut columns = key_columns
+        .iter()
+        .map(|(name, ty)| format!("{} {} NOT NULL", name, ty))
+        .collect::<Vec<_>>();
+    columns.extend(other_columns.iter().map(|column| column.to_string()));
+    let key_names = key_columns
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+    Ok(format!(
+        "CREATE TABLE {} ( {}, PRIMARY KEY ({}) ) WITHOUT ROWID, STRICT",
+        table_name,
+        columns.join(", "),
+        key_names.join(", "),
+    ))
+}
+
+/// Fill `id_column` of `table_name` with the [`DescriptorId`] of the descriptor in
+/// `descriptor_column`.
+///
+/// Descriptor ids are derived in Rust, so a migration that adds a descriptor id column can't
+/// populate it in SQL alone. Run this after the migration script, within the same `db_tx`. Every
+/// row is updated, so rows with an existing id are overwritten with the re-derived one. Returns
+/// the number of rows updated.
+#[cfg(feature = "miniscript")]
+pub fn backfill_descriptor_ids(
+    db_tx: &Transaction,
+    table_name: &str,
+    descriptor_column: &str,
+    id_column: &str,
+) -> rusqlite::Result<usize> {
+    check_identifiers(&[table_name, descriptor_column, id_column])?;
+    let mut select_statement = db_tx.prepare(&format!(
+        "SELECT DISTINCT {} FROM {}",
+        descriptor_column, table_name,
+    ))?;
+    let row_iter = select_statement.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Impl<miniscript::Descriptor<miniscript::DescriptorPublicKey>>>(0)?,
+        ))
+    })?;
+    let mut update_statement = db_tx.prepare(&format!(
+        "UPDATE {} SET {}=:id WHERE {}=:descriptor",
+        table_name, id_column, descriptor_column,
+    ))?;
+    let mut updated = 0;
+    for row in row_iter {
+        let (descriptor_text, Impl(descriptor)) = row?;
+        updated += update_statement.execute(named_params! {
+            ":id": Impl(descriptor.descriptor_id()),
+            ":descriptor": descriptor_text,
+        })?;
+    }
+    Ok(updated)
+}
+
+/// Table name for the progress of [`backfill_in_batches`].
+pub const BACKFILLS_TABLE_NAME: &str = "bdk_backfills";
+
+/// Run the data backfill named `backfill_name` as a series of separately committed batches.
+///
+/// A backfill that rewrites many rows in one transaction blocks every other writer (and, with a
+/// rollback journal, every reader) until it commits. Instead, `batch` is called repeatedly, each
+/// time in its own `IMMEDIATE` transaction that is committed before the next call, so that other
+/// connections can make progress in between (readers can do so throughout in WAL mode).
+///
+/// `batch` is given the cursor returned by the previous batch (`None` for the first one), and
+/// returns the cursor to continue from, or `None` once there is nothing left to do. The cursor
+/// would usually be the last rowid processed, with batches selecting rows `WHERE rowid > cursor
+/// ORDER BY rowid LIMIT n`. It is stored in [`BACKFILLS_TABLE_NAME`] in the same transaction as
+/// the batch's writes, so calling this again after an interruption resumes after the last
+/// committed batch, and a completed backfill is not run again. Returns the number of batches
+/// committed by this call.
+///
+/// This gives up the atomicity of a single-transaction migration for availability: other
+/// connections can observe the table half backfilled, so readers must handle both the old and
+/// the new state of a row (e.g. a new column that is still `NULL`). Run the backfill after the
+/// schema change that adds the columns it fills, not as part of the migration script.
+pub fn backfill_in_batches<F>(
+    conn: &mut rusqlite::Connection,
+    backfill_name: &str,
+    mut batch: F,
+) -> rusqlite::Result<usize>
+where
+    F: FnMut(&Transaction, Option<i64>) -> rusqlite::Result<Option<i64>>,
+{
+    let mut committed = 0;
+    loop {
+        let db_tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        db_tx.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {}( \
+                name TEXT PRIMARY KEY NOT NULL, \
+                cursor INTEGER, \
+                is_complete INTEGER NOT NULL \
+                ) STRICT",
+                BACKFILLS_TABLE_NAME,
+            ),
+            (),
+        )?;
+        let progress = db_tx
+            .query_row(
+                &format!(
+                    "SELECT cursor, is_complete FROM {} WHERE name=:name",
+                    BACKFILLS_TABLE_NAME,
+                ),
+                named_params! { ":name": backfill_name },
+                |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, bool>(1)?)),
+            )
+            .optional()?;
+        let cursor = match progress {
+            Some((_, true)) => return Ok(committed),
+            Some((cursor, false)) => cursor,
+            None => None,
+        };
+        let next_cursor = batch(&db_tx, cursor)?;
+        db_tx.execute(
+            &upsert_sql(BACKFILLS_TABLE_NAME, &["name"], &["cursor", "is_complete"])
+                .map_err(to_sql_error)?,
+            named_params! {
+                ":name": backfill_name,
+                ":cursor": next_cursor.or(cursor),
+                ":is_complete": next_cursor.is_none(),
+            },
+        )?;
+        db_tx.commit()?;
+        committed += 1;
+        if next_cursor.is_none() {
+            return Ok(committed);
+        }
+    }
+}
+
+/// Returns whether `table_name` has a column named `column_name`.
+fn column_exists(
+    db_tx: &Transaction,
+    table_name: &str,
+    column_name: &str,
+) -> rusqlite::Result<bool> {
+    db_tx
+        .query_row(
+            "SELECT 1 FROM pragma_table_info(:table) WHERE name=:column",
+            named_params! { ":table": table_name, ":column": column_name },
+            |_| Ok(()),
+        )
+        .optional()
+        .map(|row| row.is_some())
+}
+
+/// Copy the values of `from_column` into `to_column` for every row of `table_name`.
+///
+/// This is the data-moving step of replacing a column (add the new column, copy, then drop the
+/// old one). Both columns must exist. Returns the number of rows copied.
+pub fn copy_column(
+    db_tx: &Transaction,
+    table_name: &str,
+    from_column: &str,
+    to_column: &str,
+) -> rusqlite::Result<usize> {
+    check_identifiers(&[table_name, from_column, to_column])?;
+    for column in [from_column, to_column] {
+        if !column_exists(db_tx, table_name, column)? {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+                Some(format!(
+                    "cannot copy column, {}.{} does not exist",
+                    table_name, column
+                )),
+            ));
+        }
+    }
+    db_tx.execute(
+        &format!("UPDATE {} SET {}={}", table_name, to_column, from_column),
+        (),
+    )
+}
+
+/// Add `column_name` of `column_type` to `table_name`, filling existing rows with `default_sql`.
+///
+/// `column_type` may include constraints (e.g. `"INTEGER NOT NULL"`), and `default_sql` must be
+/// a constant SQL expression (e.g. `"-1"` or `"'unknown'"`). Returns an error if the column
+/// already exists, rather than failing halfway through a migration script.
+pub fn add_column_with_default(
+    db_tx: &Transaction,
+    table_name: &str,
+    column_name: &str,
+    column_type: &str,
+    default_sql: &str,
+) -> rusqlite::Result<()> {
+    check_identifiers(&[table_name, column_name])?;
+    if column_exists(db_tx, table_name, column_name)? {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+            Some(format!(
+                "cannot add column, {}.{} already exists",
+                table_name, column_name
+            )),
+        ));
     }
+    db_tx.execute(
+        &format!(
+            "ALTER TABLE {} ADD COLUMN {} {} DEFAULT {}",
+            table_name, column_name, column_type, default_sql
+        ),
+        (),
+    )?;
     Ok(())
 }
 
+/// Delete rows of `table_name` that duplicate another row's `key_columns`, keeping one per key.
+///
+/// This recovers legacy tables that lack the constraint that should have prevented duplicates.
+/// The row with the highest rowid (usually the last inserted) is kept, as that is the row an
+/// upsert would have produced. `NULL` keys count as equal. The table must have a rowid, so this
+/// can't be used on `WITHOUT ROWID` tables (which always have a primary key anyway). Returns the
+/// number of rows deleted.
+pub fn dedupe_table(
+    db_tx: &Transaction,
+    table_name: &str,
+    key_columns: &[&str],
+) -> rusqlite::Result<usize> {
+    if key_columns.is_empty() {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+            Some(format!("cannot dedupe {} without key columns", table_name)),
+        ));
+    }
+    check_identifiers(&[table_name])?;
+    check_identifiers(key_columns)?;
+    db_tx.execute(
+        &format!(
+            "DELETE FROM {table} WHERE rowid NOT IN (SELECT MAX(rowid) FROM {table} GROUP BY {keys})",
+            table = table_name,
+            keys = key_columns.join(", "),
+        ),
+        (),
+    )
+}
+
+/// Get v0 of the schema for a table that stores whole changesets as single JSON rows.
+///
+/// Use this with [`load_merged_row`] and [`persist_merged_row`]. Errors if `table_name` is not a
+/// valid identifier.
+#[cfg(feature = "json")]
+pub fn merged_row_table_schema(table_name: &str) -> Result<String, InvalidIdentifier> {
+    serde_blob::table_schema::<Json>(table_name)
+}
+
+/// Load the changeset stored as a single JSON row under `id` in `table_name`.
+///
+/// Returns `None` if no row exists for `id`. The table is expected to be created with
+/// [`merged_row_table_schema`].
+#[cfg(feature = "json")]
+pub fn load_merged_row<CS: serde::de::DeserializeOwned>(
+    db_tx: &Transaction,
+    table_name: &str,
+    id: &str,
+) -> rusqlite::Result<Option<CS>> {
+    serde_blob::load_row::<CS, Json>(db_tx, table_name, id)
+}
+
+/// Merge `incoming` into the changeset stored as a single JSON row under `id` in `table_name`.
+///
+/// The existing changeset (if any) is read, `incoming` is [merged](Merge::merge) into it and the
+/// result is written back, so the stored row always represents the aggregate of everything
+/// persisted under `id`. All of this happens within `db_tx`. The table is expected to be created
+/// with [`merged_row_table_schema`].
+#[cfg(feature = "json")]
+pub fn persist_merged_row<CS>(
+    db_tx: &Transaction,
+    table_name: &str,
+    id: &str,
+    incoming: &CS,
+) -> rusqlite::Result<()>
+where
+    CS: Merge + Clone + serde::Serialize + serde::de::DeserializeOwned,
+{
+    serde_blob::persist_row::<CS, Json>(db_tx, table_name, id, incoming)
+}
+
+/// A changeset that is persisted as a single JSON row, merging on write.
+///
+/// This is a zero-schema-design starting point for prototyping a new changeset type. Each
+/// `table_name` holds one changeset and doubles as its schema name. Once the changeset's shape
+/// stabilizes, it can be replaced with a normalized store.
+///
+/// This is a wrapper of [`SerdeBlob`] with the [`Json`] format, and reads and writes the same rows.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JsonBlob<CS>(pub CS);
+
+#[cfg(feature = "json")]
+impl<CS> JsonBlob<CS>
+where
+    CS: Default + Merge + Clone + serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Initialize the sqlite table named `table_name`.
+    pub fn init_sqlite_tables(db_tx: &Transaction, table_name: &str) -> rusqlite::Result<()> {
+        SerdeBlob::<CS, Json>::init_sqlite_tables(db_tx, table_name)
+    }
+
+    /// Load the changeset from `table_name`, or the default changeset if none was persisted.
+    pub fn from_sqlite(db_tx: &Transaction, table_name: &str) -> rusqlite::Result<Self> {
+        SerdeBlob::<CS, Json>::from_sqlite(db_tx, table_name).map(|blob| Self(blob.into_inner()))
+    }
+
+    /// Merge the changeset into the one stored in `table_name`.
+    pub fn persist_to_sqlite(&self, db_tx: &Transaction, table_name: &str) -> rusqlite::Result<()> {
+        SerdeBlob::<CS, Json>::persist_changeset(db_tx, table_name, &self.0)
+    }
+}
+
 impl FromSql for Impl<bitcoin::Txid> {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
         bitcoin::Txid::from_str(value.as_str()?)
             .map(Self)
-            .map_err(from_sql_error)
+            .map_err(Self::decode_error)
     }
 }
 
@@ -88,11 +896,25 @@ impl ToSql for Impl<bitcoin::Txid> {
     }
 }
 
+impl FromSql for Impl<bitcoin::Wtxid> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        bitcoin::Wtxid::from_str(value.as_str()?)
+            .map(Self)
+            .map_err(Self::decode_error)
+    }
+}
+
+impl ToSql for Impl<bitcoin::Wtxid> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.to_string().into())
+    }
+}
+
 impl FromSql for Impl<bitcoin::BlockHash> {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
         bitcoin::BlockHash::from_str(value.as_str()?)
             .map(Self)
-            .map_err(from_sql_error)
+            .map_err(Self::decode_error)
     }
 }
 
@@ -107,7 +929,7 @@ impl FromSql for Impl<DescriptorId> {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
         DescriptorId::from_str(value.as_str()?)
             .map(Self)
-            .map_err(from_sql_error)
+            .map_err(Self::decode_error)
     }
 }
 
@@ -122,7 +944,7 @@ impl FromSql for Impl<bitcoin::Transaction> {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
         bitcoin::Transaction::consensus_decode_from_finite_reader(&mut value.as_bytes()?)
             .map(Self)
-            .map_err(from_sql_error)
+            .map_err(Self::decode_error)
     }
 }
 
@@ -134,496 +956,2987 @@ impl ToSql for Impl<bitcoin::Transaction> {
     }
 }
 
-impl FromSql for Impl<bitcoin::ScriptBuf> {
+impl FromSql for Impl<bitcoin::Witness> {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
-        Ok(bitcoin::Script::from_bytes(value.as_bytes()?)
-            .to_owned()
-            .into())
+        bitcoin::Witness::consensus_decode_from_finite_reader(&mut value.as_bytes()?)
+            .map(Self)
+            .map_err(Self::decode_error)
     }
 }
 
-impl ToSql for Impl<bitcoin::ScriptBuf> {
+impl ToSql for Impl<bitcoin::Witness> {
     fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
-        Ok(self.as_bytes().into())
+        let mut bytes = Vec::<u8>::new();
+        self.consensus_encode(&mut bytes).map_err(to_sql_error)?;
+        Ok(bytes.into())
     }
 }
 
-impl FromSql for Impl<bitcoin::Amount> {
+impl FromSql for Impl<bitcoin::block::Header> {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
-        Ok(bitcoin::Amount::from_sat(value.as_i64()?.try_into().map_err(from_sql_error)?).into())
+        const HEADER_SIZE: usize = 80;
+        let bytes = value.as_blob()?;
+        if bytes.len() != HEADER_SIZE {
+            return Err(FromSqlError::InvalidBlobSize {
+                expected_size: HEADER_SIZE,
+                blob_size: bytes.len(),
+            });
+        }
+        bitcoin::block::Header::consensus_decode(&mut &*bytes)
+            .map(Self)
+            .map_err(Self::decode_error)
     }
 }
 
-impl ToSql for Impl<bitcoin::Amount> {
+impl ToSql for Impl<bitcoin::block::Header> {
     fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
-        let amount: i64 = self.to_sat().try_into().map_err(to_sql_error)?;
-        Ok(amount.into())
+        let mut bytes = Vec::<u8>::new();
+        self.consensus_encode(&mut bytes).map_err(to_sql_error)?;
+        Ok(bytes.into())
     }
 }
 
-#[cfg(feature = "miniscript")]
-impl FromSql for Impl<miniscript::Descriptor<miniscript::DescriptorPublicKey>> {
+/// Maximum size in bytes of a block BLOB that [`Impl<bitcoin::Block>`] will attempt to decode.
+///
+/// A consensus-valid block can never serialize to more than its maximum weight in bytes, so
+/// larger BLOBs are rejected before decoding.
+pub const MAX_BLOCK_BLOB_SIZE: usize = bitcoin::Weight::MAX_BLOCK.to_wu() as usize;
+
+impl FromSql for Impl<bitcoin::Block> {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
-        miniscript::Descriptor::from_str(value.as_str()?)
+        let bytes = value.as_blob()?;
+        if bytes.len() > MAX_BLOCK_BLOB_SIZE {
+            return Err(FromSqlError::InvalidBlobSize {
+                expected_size: MAX_BLOCK_BLOB_SIZE,
+                blob_size: bytes.len(),
+            });
+        }
+        bitcoin::Block::consensus_decode_from_finite_reader(&mut &*bytes)
             .map(Self)
-            .map_err(from_sql_error)
+            .map_err(Self::decode_error)
     }
 }
 
-#[cfg(feature = "miniscript")]
-impl ToSql for Impl<miniscript::Descriptor<miniscript::DescriptorPublicKey>> {
+impl ToSql for Impl<bitcoin::Block> {
     fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
-        Ok(self.to_string().into())
+        let mut bytes = Vec::<u8>::new();
+        self.consensus_encode(&mut bytes).map_err(to_sql_error)?;
+        Ok(bytes.into())
     }
 }
 
-impl FromSql for Impl<bitcoin::Network> {
+impl FromSql for Impl<bitcoin::taproot::TapNodeHash> {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
-        bitcoin::Network::from_str(value.as_str()?)
-            .map(Self)
-            .map_err(from_sql_error)
+        use bitcoin::hashes::Hash;
+        let bytes = value.as_blob()?;
+        <[u8; 32]>::try_from(bytes)
+            .map(|bytes| Self(bitcoin::taproot::TapNodeHash::from_byte_array(bytes)))
+            .map_err(|_| FromSqlError::InvalidBlobSize {
+                expected_size: 32,
+                blob_size: bytes.len(),
+            })
     }
 }
 
-impl ToSql for Impl<bitcoin::Network> {
+impl ToSql for Impl<bitcoin::taproot::TapNodeHash> {
     fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
-        Ok(self.to_string().into())
+        use bitcoin::hashes::Hash;
+        Ok(self.as_byte_array().to_vec().into())
     }
 }
 
-fn from_sql_error<E: std::error::Error + Send + Sync + 'static>(err: E) -> FromSqlError {
-    FromSqlError::Other(Box::new(err))
+impl FromSql for Impl<bitcoin::ScriptHash> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        use bitcoin::hashes::Hash;
+        let bytes = value.as_blob()?;
+        <[u8; 20]>::try_from(bytes)
+            .map(|bytes| Self(bitcoin::ScriptHash::from_byte_array(bytes)))
+            .map_err(|_| FromSqlError::InvalidBlobSize {
+                expected_size: 20,
+                blob_size: bytes.len(),
+            })
+    }
 }
 
-fn to_sql_error<E: std::error::Error + Send + Sync + 'static>(err: E) -> rusqlite::Error {
-    rusqlite::Error::ToSqlConversionFailure(Box::new(err))
+impl ToSql for Impl<bitcoin::ScriptHash> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        use bitcoin::hashes::Hash;
+        Ok(self.as_byte_array().to_vec().into())
+    }
 }
 
-impl tx_graph::ChangeSet<ConfirmationBlockTime> {
-    /// Schema name for [`tx_graph::ChangeSet`].
-    pub const SCHEMA_NAME: &'static str = "bdk_txgraph";
-    /// Name of table that stores full transactions and `last_seen` timestamps.
-    pub const TXS_TABLE_NAME: &'static str = "bdk_txs";
-    /// Name of table that stores floating txouts.
-    pub const TXOUTS_TABLE_NAME: &'static str = "bdk_txouts";
-    /// Name of table that stores [`Anchor`]s.
-    pub const ANCHORS_TABLE_NAME: &'static str = "bdk_anchors";
+impl FromSql for Impl<bitcoin::WScriptHash> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        use bitcoin::hashes::Hash;
+        let bytes = value.as_blob()?;
+        <[u8; 32]>::try_from(bytes)
+            .map(|bytes| Self(bitcoin::WScriptHash::from_byte_array(bytes)))
+            .map_err(|_| FromSqlError::InvalidBlobSize {
+                expected_size: 32,
+                blob_size: bytes.len(),
+            })
+    }
+}
 
-    /// Get v0 of sqlite [tx_graph::ChangeSet] schema
-    pub fn schema_v0() -> String {
-        // full transactions
-        let create_txs_table = format!(
-            "CREATE TABLE {} ( \
-            txid TEXT PRIMARY KEY NOT NULL, \
-            raw_tx BLOB, \
-            last_seen INTEGER \
-            ) STRICT",
-            Self::TXS_TABLE_NAME,
-        );
-        // floating txouts
-        let create_txouts_table = format!(
-            "CREATE TABLE {} ( \
-            txid TEXT NOT NULL, \
-            vout INTEGER NOT NULL, \
-            value INTEGER NOT NULL, \
-            script BLOB NOT NULL, \
-            PRIMARY KEY (txid, vout) \
-            ) STRICT",
-            Self::TXOUTS_TABLE_NAME,
-        );
-        // anchors
-        let create_anchors_table = format!(
-            "CREATE TABLE {} ( \
-            txid TEXT NOT NULL REFERENCES {} (txid), \
-            block_height INTEGER NOT NULL, \
-            block_hash TEXT NOT NULL, \
-            anchor BLOB NOT NULL, \
-            PRIMARY KEY (txid, block_height, block_hash) \
-            ) STRICT",
-            Self::ANCHORS_TABLE_NAME,
-            Self::TXS_TABLE_NAME,
-        );
+impl ToSql for Impl<bitcoin::WScriptHash> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        use bitcoin::hashes::Hash;
+        Ok(self.as_byte_array().to_vec().into())
+    }
+}
 
-        format!("{create_txs_table}; {create_txouts_table}; {create_anchors_table}")
+impl FromSql for Impl<bitcoin::taproot::ControlBlock> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        bitcoin::taproot::ControlBlock::decode(value.as_blob()?)
+            .map(Self)
+            .map_err(Self::decode_error)
     }
+}
 
-    /// Get v1 of sqlite [tx_graph::ChangeSet] schema
-    pub fn schema_v1() -> String {
-        let add_confirmation_time_column = format!(
-            "ALTER TABLE {} ADD COLUMN confirmation_time INTEGER DEFAULT -1 NOT NULL",
-            Self::ANCHORS_TABLE_NAME,
-        );
-        let extract_confirmation_time_from_anchor_column = format!(
-            "UPDATE {} SET confirmation_time = json_extract(anchor, '$.confirmation_time')",
-            Self::ANCHORS_TABLE_NAME,
+impl ToSql for Impl<bitcoin::taproot::ControlBlock> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.serialize().into())
+    }
+}
+
+impl FromSql for Impl<bitcoin::CompressedPublicKey> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        const COMPRESSED_KEY_SIZE: usize = 33;
+        let bytes = value.as_blob()?;
+        // `from_slice` also accepts (and compresses) uncompressed encodings
+        if bytes.len() != COMPRESSED_KEY_SIZE {
+            return Err(FromSqlError::InvalidBlobSize {
+                expected_size: COMPRESSED_KEY_SIZE,
+                blob_size: bytes.len(),
+            });
+        }
+        bitcoin::CompressedPublicKey::from_slice(bytes)
+            .map(Self)
+            .map_err(Self::decode_error)
+    }
+}
+
+impl ToSql for Impl<bitcoin::CompressedPublicKey> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.to_bytes().to_vec().into())
+    }
+}
+
+impl FromSql for Impl<bitcoin::Sequence> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        Ok(Self(bitcoin::Sequence(u32::column_result(value)?)))
+    }
+}
+
+impl ToSql for Impl<bitcoin::Sequence> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.0 .0.into())
+    }
+}
+
+impl FromSql for Impl<bitcoin::transaction::Version> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        Ok(Self(bitcoin::transaction::Version(i32::column_result(
+            value,
+        )?)))
+    }
+}
+
+impl ToSql for Impl<bitcoin::transaction::Version> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.0 .0.into())
+    }
+}
+
+/// Whether a [`Sequence`](bitcoin::Sequence) or transaction signals replaceability (BIP125),
+/// stored as an `INTEGER` boolean (`0` or `1`).
+///
+/// This lets a table record whether a transaction is replaceable, so that it can be queried
+/// (e.g. `WHERE rbf_signaled`) without decoding stored transactions. Values are created with
+/// [`Sequence::is_rbf`](bitcoin::Sequence::is_rbf) or
+/// [`Transaction::is_explicitly_rbf`](bitcoin::Transaction::is_explicitly_rbf).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SqlRbfSignaled(pub bool);
+
+impl From<bitcoin::Sequence> for SqlRbfSignaled {
+    fn from(sequence: bitcoin::Sequence) -> Self {
+        Self(sequence.is_rbf())
+    }
+}
+
+impl From<&bitcoin::Transaction> for SqlRbfSignaled {
+    fn from(tx: &bitcoin::Transaction) -> Self {
+        Self(tx.is_explicitly_rbf())
+    }
+}
+
+impl FromSql for SqlRbfSignaled {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_i64()? {
+            0 => Ok(Self(false)),
+            1 => Ok(Self(true)),
+            other => Err(FromSqlError::OutOfRange(other)),
+        }
+    }
+}
+
+impl ToSql for SqlRbfSignaled {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.0.into())
+    }
+}
+
+impl FromSql for Impl<bitcoin::ScriptBuf> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        Ok(bitcoin::Script::from_bytes(value.as_bytes()?)
+            .to_owned()
+            .into())
+    }
+}
+
+impl ToSql for Impl<bitcoin::ScriptBuf> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_bytes().into())
+    }
+}
+
+/// A wrapper that stores the inner value as a lowercase hex string (`TEXT`) instead of a `BLOB`.
+///
+/// This is meant for columns that need to be human-readable or joined against hex-based external
+/// tables. The hex form takes twice the space of the raw bytes that [`Impl`] stores, so prefer
+/// [`Impl`] unless interoperability is needed.
+pub struct SqlHex<T>(pub T);
+
+impl<T> SqlHex<T> {
+    /// Returns the inner `T`.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for SqlHex<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl FromSql for SqlHex<bitcoin::ScriptBuf> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        bitcoin::ScriptBuf::from_hex(value.as_str()?)
+            .map(Self)
+            .map_err(Self::decode_error)
+    }
+}
+
+impl ToSql for SqlHex<bitcoin::ScriptBuf> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.0.to_hex_string().into())
+    }
+}
+
+impl FromSql for Impl<core::num::NonZeroU32> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let value = u32::column_result(value)?;
+        core::num::NonZeroU32::new(value)
+            .map(Self)
+            .ok_or(FromSqlError::OutOfRange(value.into()))
+    }
+}
+
+impl ToSql for Impl<core::num::NonZeroU32> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.get().into())
+    }
+}
+
+/// A wrapper that stores an integer as `INTEGER` and checks that it lies within `MIN..=MAX`.
+///
+/// This keeps the invariant of bounded fields (e.g. a gap limit) across a round trip, so that an
+/// invalid stored value is rejected at load time with [`FromSqlError::OutOfRange`]. Writing an
+/// out-of-range value fails too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SqlBounded<T, const MIN: i64, const MAX: i64>(pub T);
+
+impl<T, const MIN: i64, const MAX: i64> SqlBounded<T, MIN, MAX> {
+    /// Returns the inner `T`.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T, const MIN: i64, const MAX: i64> From<T> for SqlBounded<T, MIN, MAX> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T, const MIN: i64, const MAX: i64> FromSql for SqlBounded<T, MIN, MAX>
+where
+    T: FromSql,
+{
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let bounded = value.as_i64()?;
+        if !(MIN..=MAX).contains(&bounded) {
+            return Err(FromSqlError::OutOfRange(bounded));
+        }
+        T::column_result(value).map(Self)
+    }
+}
+
+impl<T, const MIN: i64, const MAX: i64> ToSql for SqlBounded<T, MIN, MAX>
+where
+    T: Copy + Into<i64>,
+{
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let bounded: i64 = self.0.into();
+        if !(MIN..=MAX).contains(&bounded) {
+            return Err(to_sql_error(FromSqlError::OutOfRange(bounded)));
+        }
+        Ok(bounded.into())
+    }
+}
+
+/// A wrapper that stores a string as `TEXT` and checks that it is at most `N` bytes long.
+///
+/// This bounds user-supplied text such as labels and memos at the persistence boundary, so that
+/// a pathological input can't bloat the database. Writing a longer string fails with a
+/// [`TextTooLongError`], and so does loading one (e.g. written by another application).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SqlBoundedText<const N: usize>(pub String);
+
+impl<const N: usize> SqlBoundedText<N> {
+    /// Returns the inner string.
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+
+    fn check_len(text: &str) -> Result<(), TextTooLongError> {
+        if text.len() > N {
+            return Err(TextTooLongError {
+                max_len: N,
+                len: text.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize> From<String> for SqlBoundedText<N> {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl<const N: usize> FromSql for SqlBoundedText<N> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let text = value.as_str()?;
+        Self::check_len(text).map_err(|err| FromSqlError::Other(Box::new(err)))?;
+        Ok(Self(text.to_owned()))
+    }
+}
+
+impl<const N: usize> ToSql for SqlBoundedText<N> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Self::check_len(&self.0).map_err(to_sql_error)?;
+        Ok(self.0.as_str().into())
+    }
+}
+
+/// Error of a string that is longer than the limit of a [`SqlBoundedText`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextTooLongError {
+    /// Maximum length in bytes.
+    pub max_len: usize,
+    /// Length of the string in bytes.
+    pub len: usize,
+}
+
+impl core::fmt::Display for TextTooLongError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "text of {} bytes exceeds the limit of {} bytes",
+            self.len, self.max_len
+        )
+    }
+}
+
+impl std::error::Error for TextTooLongError {}
+
+impl FromSql for Impl<bitcoin::Amount> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        Ok(
+            bitcoin::Amount::from_sat(value.as_i64()?.try_into().map_err(Self::decode_error)?)
+                .into(),
+        )
+    }
+}
+
+impl ToSql for Impl<bitcoin::Amount> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let amount: i64 = self.to_sat().try_into().map_err(to_sql_error)?;
+        Ok(amount.into())
+    }
+}
+
+/// A wrapper that stores any consensus-encodable type (e.g. [`TxOut`](bitcoin::TxOut)) as a BLOB
+/// of its consensus encoding.
+///
+/// Loading fails if the BLOB has trailing bytes after the encoded value.
+pub struct SqlConsensus<T>(pub T);
+
+impl<T> SqlConsensus<T> {
+    /// Returns the inner `T`.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for SqlConsensus<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> SqlDecode for SqlConsensus<T> {
+    type Target = T;
+}
+
+impl<T: Decodable> FromSql for SqlConsensus<T> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        bitcoin::consensus::deserialize(value.as_blob()?)
+            .map(Self)
+            .map_err(Self::decode_error)
+    }
+}
+
+impl<T: Encodable> ToSql for SqlConsensus<T> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(bitcoin::consensus::serialize(&self.0).into())
+    }
+}
+
+/// A wrapper that stores a [`FeeRate`](bitcoin::FeeRate) as a `REAL` number of sat/vB.
+///
+/// This is meant for interoperability with tools that store fractional sat/vB rates. Rates are
+/// reconstructed with sat/kwu precision (1 sat/kwu = 0.004 sat/vB), rounding to the nearest value.
+pub struct SqlSatPerVb(pub bitcoin::FeeRate);
+
+impl FromSql for SqlSatPerVb {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let sat_per_vb = value.as_f64()?;
+        let sat_per_kwu = (sat_per_vb * 250.0).round();
+        if !(0.0..=u64::MAX as f64).contains(&sat_per_kwu) {
+            return Err(FromSqlError::OutOfRange(sat_per_vb as i64));
+        }
+        Ok(Self(bitcoin::FeeRate::from_sat_per_kwu(sat_per_kwu as u64)))
+    }
+}
+
+impl ToSql for SqlSatPerVb {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok((self.0.to_sat_per_kwu() as f64 / 250.0).into())
+    }
+}
+
+/// A wrapper that stores an [`Amount`](bitcoin::Amount) as `TEXT` with exactly 8 decimal places
+/// of BTC (e.g. `"0.00050000"`).
+///
+/// This is meant for report and export tables that are read by humans. Parsing uses integer
+/// arithmetic, so it is lossless, and rejects values with more than 8 decimal places.
+pub struct SqlBtc(pub bitcoin::Amount);
+
+impl SqlDecode for SqlBtc {
+    type Target = bitcoin::Amount;
+}
+
+impl FromSql for SqlBtc {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        bitcoin::Amount::from_str_in(value.as_str()?, bitcoin::Denomination::Bitcoin)
+            .map(Self)
+            .map_err(Self::decode_error)
+    }
+}
+
+impl ToSql for SqlBtc {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        const SAT_PER_BTC: u64 = 100_000_000;
+        let sat = self.0.to_sat();
+        Ok(format!("{}.{:08}", sat / SAT_PER_BTC, sat % SAT_PER_BTC).into())
+    }
+}
+
+#[cfg(feature = "miniscript")]
+impl FromSql for Impl<miniscript::Descriptor<miniscript::DescriptorPublicKey>> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        miniscript::Descriptor::from_str(value.as_str()?)
+            .map(Self)
+            .map_err(Self::decode_error)
+    }
+}
+
+/// Descriptors are stored in their canonical string form (with checksum), so two descriptors that
+/// parse to the same value are stored as the same string and can be compared with SQLite's
+/// default (case-sensitive) collation. In particular, hex-encoded keys are always written in
+/// lowercase. Base58 extended keys are case-sensitive and are never case-normalized, so a
+/// case-insensitive collation must not be used on descriptor columns.
+#[cfg(feature = "miniscript")]
+impl ToSql for Impl<miniscript::Descriptor<miniscript::DescriptorPublicKey>> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.to_string().into())
+    }
+}
+
+impl FromSql for Impl<bitcoin::Network> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        bitcoin::Network::from_str(value.as_str()?)
+            .map(Self)
+            .map_err(Self::decode_error)
+    }
+}
+
+impl ToSql for Impl<bitcoin::Network> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.to_string().into())
+    }
+}
+
+impl FromSql for Impl<bitcoin::bip32::DerivationPath> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        bitcoin::bip32::DerivationPath::from_str(value.as_str()?)
+            .map(Self)
+            .map_err(Self::decode_error)
+    }
+}
+
+impl ToSql for Impl<bitcoin::bip32::DerivationPath> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        // `Display` leaves out the `m/` prefix of the usual string form
+        if self.is_master() {
+            Ok("m".into())
+        } else {
+            Ok(format!("m/{}", self.0).into())
+        }
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl FromSql for Impl<uuid::Uuid> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let bytes = value.as_blob()?;
+        let bytes = <[u8; 16]>::try_from(bytes).map_err(|_| FromSqlError::InvalidBlobSize {
+            expected_size: 16,
+            blob_size: bytes.len(),
+        })?;
+        Ok(Self(uuid::Uuid::from_bytes(bytes)))
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl ToSql for Impl<uuid::Uuid> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_bytes()[..].into())
+    }
+}
+
+/// Get the value of the column named `column_name` from `row`.
+///
+/// This behaves like [`rusqlite::Row::get`], except that a failed conversion reports the name of
+/// the column that failed (instead of only its index). Prefer this over `row.get` in the
+/// `query_map` closures of loaders that read several columns. Reading by name also keeps a loader
+/// correct when the table has extra columns at any position.
+pub fn get_sql<T: FromSql>(row: &rusqlite::Row, column_name: &str) -> rusqlite::Result<T> {
+    row.get(column_name).map_err(|err| match err {
+        rusqlite::Error::FromSqlConversionFailure(index, ty, source) => {
+            rusqlite::Error::FromSqlConversionFailure(
+                index,
+                ty,
+                Box::new(ColumnError {
+                    column_name: column_name.to_owned(),
+                    source,
+                }),
+            )
+        }
+        err => err,
+    })
+}
+
+/// A conversion error annotated with the name of the column it occurred in.
+#[derive(Debug)]
+struct ColumnError {
+    column_name: String,
+    source: Box<dyn std::error::Error + Send + Sync + 'static>,
+}
+
+impl core::fmt::Display for ColumnError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "column `{}`: {}", self.column_name, self.source)
+    }
+}
+
+impl std::error::Error for ColumnError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// A [`FromSql`] wrapper whose decode failures name the type being decoded.
+trait SqlDecode {
+    /// The type that column values are decoded into.
+    type Target;
+
+    /// Wrap `err` in a [`DecodeError`] naming [`Self::Target`].
+    fn decode_error<E: std::error::Error + Send + Sync + 'static>(err: E) -> FromSqlError {
+        FromSqlError::Other(Box::new(DecodeError {
+            type_name: core::any::type_name::<Self::Target>(),
+            source: Box::new(err),
+        }))
+    }
+}
+
+impl<T> SqlDecode for Impl<T> {
+    type Target = T;
+}
+
+impl<T> SqlDecode for SqlHex<T> {
+    type Target = T;
+}
+
+/// Error of decoding a column value into a specific type.
+///
+/// This is the error that conversions of this module wrap in [`FromSqlError::Other`] when a
+/// column value cannot be parsed.
+#[derive(Debug)]
+pub struct DecodeError {
+    type_name: &'static str,
+    source: Box<dyn std::error::Error + Send + Sync + 'static>,
+}
+
+impl DecodeError {
+    /// Name of the type that failed to decode, as given by [`core::any::type_name`].
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "failed to decode {}: {}", self.type_name, self.source)
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// Error of a stored transaction that can't be decoded.
+///
+/// Unlike a plain [`DecodeError`], this names the row by its txid so it can be found, deleted
+/// and re-fetched from the network. Returned by [`decode_tx_for_txid`].
+#[derive(Debug)]
+pub struct CorruptTxError {
+    txid: bitcoin::Txid,
+    blob_size: usize,
+    source: bitcoin::consensus::encode::Error,
+}
+
+impl CorruptTxError {
+    /// Txid that the transaction is stored under.
+    pub fn txid(&self) -> bitcoin::Txid {
+        self.txid
+    }
+
+    /// Size of the stored blob in bytes.
+    pub fn blob_size(&self) -> usize {
+        self.blob_size
+    }
+}
+
+impl core::fmt::Display for CorruptTxError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "stored transaction {} ({} bytes) is corrupt: {}; the row can be deleted and the transaction re-fetched from the network",
+            self.txid, self.blob_size, self.source
+        )
+    }
+}
+
+impl std::error::Error for CorruptTxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Decode the consensus-encoded transaction stored under `txid`.
+///
+/// Loaders that know the key of a row use this instead of [`Impl<Transaction>`](Impl) so that
+/// failures are reported as a [`CorruptTxError`] naming the txid.
+pub fn decode_tx_for_txid(
+    bytes: &[u8],
+    txid: bitcoin::Txid,
+) -> Result<bitcoin::Transaction, CorruptTxError> {
+    bitcoin::Transaction::consensus_decode_from_finite_reader(&mut &*bytes).map_err(|source| {
+        CorruptTxError {
+            txid,
+            blob_size: bytes.len(),
+            source,
+        }
+    })
+}
+
+fn to_sql_error<E: std::error::Error + Send + Sync + 'static>(err: E) -> rusqlite::Error {
+    rusqlite::Error::ToSqlConversionFailure(Box::new(err))
+}
+
+/// Error of a table or column name that is not a plain SQL identifier.
+///
+/// Returned by [`validate_identifier`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidIdentifier {
+    identifier: String,
+}
+
+impl InvalidIdentifier {
+    /// The rejected identifier.
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+}
+
+impl core::fmt::Display for InvalidIdentifier {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "invalid identifier {:?}, expected a non-empty name of [A-Za-z0-9_]",
+            self.identifier
+        )
+    }
+}
+
+impl std::error::Error for InvalidIdentifier {}
+
+/// Check that `name` is a non-empty identifier made of `[A-Za-z0-9_]` only.
+///
+/// Table and column names can't be bound as statement parameters, so the helpers of this module
+/// that take them interpolate them into the SQL text and reject anything else with this check.
+/// The statement builders of this module run it on the names they are given. Call it on
+/// configurable names (e.g. prefixed or namespaced table names) before using them in statements
+/// built by other means, such as hand-written migration scripts.
+pub fn validate_identifier(name: &str) -> Result<(), InvalidIdentifier> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(InvalidIdentifier {
+            identifier: name.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// [`validate_identifier`] each of `names`.
+fn validate_identifiers<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+) -> Result<(), InvalidIdentifier> {
+    names.into_iter().try_for_each(validate_identifier)
+}
+
+/// [`validate_identifiers`], for helpers that return [`rusqlite::Result`].
+fn check_identifiers(names: &[&str]) -> rusqlite::Result<()> {
+    validate_identifiers(names.iter().copied()).map_err(to_sql_error)
+}
+
+impl tx_graph::ChangeSet<ConfirmationBlockTime> {
+    /// Schema name for [`tx_graph::ChangeSet`].
+    pub const SCHEMA_NAME: &'static str = "bdk_txgraph";
+    /// Latest version of the schema, which [`Self::init_sqlite_tables`] migrates to.
+    pub const SCHEMA_VERSION: u32 = 1;
+    /// Name of table that stores full transactions and `last_seen` timestamps.
+    pub const TXS_TABLE_NAME: &'static str = "bdk_txs";
+    /// Name of table that stores floating txouts.
+    pub const TXOUTS_TABLE_NAME: &'static str = "bdk_txouts";
+    /// Name of table that stores [`Anchor`]s.
+    pub const ANCHORS_TABLE_NAME: &'static str = "bdk_anchors";
+    /// Names of all schemas that [`Self::init_sqlite_tables`] migrates.
+    ///
+    /// Composite changesets concatenate these to check them with [`check_unique_schema_names`].
+    pub const SCHEMA_NAMES: &'static [&'static str] = &[Self::SCHEMA_NAME];
+    /// Names of all tables of the schema.
+    pub const TABLE_NAMES: &'static [&'static str] = &[
+        Self::TXS_TABLE_NAME,
+        Self::TXOUTS_TABLE_NAME,
+        Self::ANCHORS_TABLE_NAME,
+    ];
+
+    /// Get v0 of sqlite [tx_graph::ChangeSet] schema
+    pub fn schema_v0() -> String {
+        // full transactions
+        let create_txs_table = format!(
+            "CREATE TABLE {} ( \
+            txid TEXT PRIMARY KEY NOT NULL, \
+            raw_tx BLOB, \
+            last_seen INTEGER \
+            ) STRICT",
+            Self::TXS_TABLE_NAME,
+        );
+        // floating txouts
+        let create_txouts_table = format!(
+            "CREATE TABLE {} ( \
+            txid TEXT NOT NULL, \
+            vout INTEGER NOT NULL, \
+            value INTEGER NOT NULL, \
+            script BLOB NOT NULL, \
+            PRIMARY KEY (txid, vout) \
+            ) STRICT",
+            Self::TXOUTS_TABLE_NAME,
+        );
+        // anchors
+        let create_anchors_table = format!(
+            "CREATE TABLE {} ( \
+            txid TEXT NOT NULL REFERENCES {} (txid), \
+            block_height INTEGER NOT NULL, \
+            block_hash TEXT NOT NULL, \
+            anchor BLOB NOT NULL, \
+            PRIMARY KEY (txid, block_height, block_hash) \
+            ) STRICT",
+            Self::ANCHORS_TABLE_NAME,
+            Self::TXS_TABLE_NAME,
+        );
+
+        format!("{create_txs_table}; {create_txouts_table}; {create_anchors_table}")
+    }
+
+    /// Get v1 of sqlite [tx_graph::ChangeSet] schema
+    pub fn schema_v1() -> String {
+        let add_confirmation_time_column = format!(
+            "ALTER TABLE {} ADD COLUMN confirmation_time INTEGER DEFAULT -1 NOT NULL",
+            Self::ANCHORS_TABLE_NAME,
+        );
+        let extract_confirmation_time_from_anchor_column = format!(
+            "UPDATE {} SET confirmation_time = json_extract(anchor, '$.confirmation_time')",
+            Self::ANCHORS_TABLE_NAME,
+        );
+        let drop_anchor_column = format!(
+            "ALTER TABLE {} DROP COLUMN anchor",
+            Self::ANCHORS_TABLE_NAME,
+        );
+        format!("{add_confirmation_time_column}; {extract_confirmation_time_from_anchor_column}; {drop_anchor_column}")
+    }
+
+    /// Initialize sqlite tables.
+    pub fn init_sqlite_tables(db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+        migrate_schema(
+            db_tx,
+            Self::SCHEMA_NAME,
+            &[&Self::schema_v0(), &Self::schema_v1()],
+        )
+    }
+
+    /// Delete all rows of the sqlite tables while keeping the tables and their schema version.
+    pub fn clear_sqlite_tables(db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+        // anchors reference txs, so they must be deleted first
+        for table_name in [
+            Self::ANCHORS_TABLE_NAME,
+            Self::TXOUTS_TABLE_NAME,
+            Self::TXS_TABLE_NAME,
+        ] {
+            db_tx.execute(&format!("DELETE FROM {}", table_name), ())?;
+        }
+        Ok(())
+    }
+
+    /// Construct a [`TxGraph`] from an sqlite database.
+    ///
+    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
+    pub fn from_sqlite(db_tx: &rusqlite::Transaction) -> rusqlite::Result<Self> {
+        let mut changeset = Self::default();
+
+        let mut statement = db_tx.prepare(&format!(
+            "SELECT txid, raw_tx, last_seen FROM {}",
+            Self::TXS_TABLE_NAME,
+        ))?;
+        let row_iter = statement.query_map([], |row| {
+            let Impl(txid) = get_sql::<Impl<bitcoin::Txid>>(row, "txid")?;
+            let tx = match row.get_ref("raw_tx")? {
+                ValueRef::Null => None,
+                raw_tx => Some(decode_tx_for_txid(raw_tx.as_blob()?, txid).map_err(|err| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        1,
+                        rusqlite::types::Type::Blob,
+                        Box::new(err),
+                    )
+                })?),
+            };
+            Ok((txid, tx, get_sql::<Option<u64>>(row, "last_seen")?))
+        })?;
+        for row in row_iter {
+            let (txid, tx, last_seen) = row?;
+            if let Some(tx) = tx {
+                changeset.txs.insert(Arc::new(tx));
+            }
+            if let Some(last_seen) = last_seen {
+                changeset.last_seen.insert(txid, last_seen);
+            }
+        }
+
+        let mut statement = db_tx.prepare(&format!(
+            "SELECT txid, vout, value, script FROM {}",
+            Self::TXOUTS_TABLE_NAME,
+        ))?;
+        let row_iter = statement.query_map([], |row| {
+            Ok((
+                get_sql::<Impl<bitcoin::Txid>>(row, "txid")?,
+                get_sql::<u32>(row, "vout")?,
+                get_sql::<Impl<bitcoin::Amount>>(row, "value")?,
+                get_sql::<Impl<bitcoin::ScriptBuf>>(row, "script")?,
+            ))
+        })?;
+        for row in row_iter {
+            let (Impl(txid), vout, Impl(value), Impl(script_pubkey)) = row?;
+            changeset.txouts.insert(
+                bitcoin::OutPoint { txid, vout },
+                bitcoin::TxOut {
+                    value,
+                    script_pubkey,
+                },
+            );
+        }
+
+        let mut statement = db_tx.prepare(&format!(
+            "SELECT block_hash, block_height, confirmation_time, txid FROM {}",
+            Self::ANCHORS_TABLE_NAME,
+        ))?;
+        let row_iter = statement.query_map([], |row| {
+            Ok((
+                get_sql::<Impl<bitcoin::BlockHash>>(row, "block_hash")?,
+                get_sql::<u32>(row, "block_height")?,
+                get_sql::<u64>(row, "confirmation_time")?,
+                get_sql::<Impl<bitcoin::Txid>>(row, "txid")?,
+            ))
+        })?;
+        for row in row_iter {
+            let (hash, height, confirmation_time, Impl(txid)) = row?;
+            changeset.anchors.insert((
+                ConfirmationBlockTime {
+                    block_id: BlockId::from((&height, &hash.0)),
+                    confirmation_time,
+                },
+                txid,
+            ));
+        }
+
+        Ok(changeset)
+    }
+
+    /// Returns the txids of anchors for which no full transaction is stored.
+    ///
+    /// Anchors may legitimately be persisted before their transaction, so the returned txids are
+    /// not necessarily a sign of corruption. However, transactions that stay missing should be
+    /// re-fetched from the chain source.
+    ///
+    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
+    pub fn anchored_txids_without_tx(
+        db_tx: &rusqlite::Transaction,
+    ) -> rusqlite::Result<Vec<bitcoin::Txid>> {
+        let mut statement = db_tx.prepare(&format!(
+            "SELECT DISTINCT a.txid FROM {} AS a LEFT JOIN {} AS t ON a.txid = t.txid \
+            WHERE t.raw_tx IS NULL ORDER BY a.txid",
+            Self::ANCHORS_TABLE_NAME,
+            Self::TXS_TABLE_NAME,
+        ))?;
+        let row_iter =
+            statement.query_map([], |row| get_sql::<Impl<bitcoin::Txid>>(row, "txid"))?;
+        row_iter.map(|row| row.map(Impl::into_inner)).collect()
+    }
+
+    /// Returns the txids of unanchored transactions that were last seen before `cutoff` (or have
+    /// never been seen).
+    ///
+    /// Such transactions are likely to have been dropped from the mempool or replaced, and are
+    /// candidates for pruning.
+    ///
+    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
+    pub fn orphaned_txids(
+        db_tx: &rusqlite::Transaction,
+        cutoff: u64,
+    ) -> rusqlite::Result<Vec<bitcoin::Txid>> {
+        let cutoff = i64::try_from(cutoff).unwrap_or(i64::MAX);
+        let mut statement = db_tx.prepare(&format!(
+            "SELECT t.txid FROM {} AS t LEFT JOIN {} AS a ON t.txid = a.txid \
+            WHERE a.txid IS NULL AND IFNULL(t.last_seen, 0) < :cutoff ORDER BY t.txid",
+            Self::TXS_TABLE_NAME,
+            Self::ANCHORS_TABLE_NAME,
+        ))?;
+        let row_iter = statement.query_map(named_params! { ":cutoff": cutoff }, |row| {
+            get_sql::<Impl<bitcoin::Txid>>(row, "txid")
+        })?;
+        row_iter.map(|row| row.map(Impl::into_inner)).collect()
+    }
+
+    /// Persist `changeset` to the sqlite database.
+    ///
+    /// Rows are written so that the stored data ends up as the [merge](Merge::merge) of what was
+    /// stored and `self`. In particular, a `last_seen` timestamp never decreases. This keeps
+    /// concurrent writers that started from the same state from overwriting each other's changes,
+    /// without having to load what is stored first.
+    ///
+    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
+    pub fn persist_to_sqlite(&self, db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+        let mut statement = db_tx.prepare_cached(&format!(
+            "INSERT INTO {}(txid, raw_tx) VALUES(:txid, :raw_tx) ON CONFLICT(txid) DO UPDATE SET raw_tx=:raw_tx",
+            Self::TXS_TABLE_NAME,
+        ))?;
+        for tx in &self.txs {
+            statement.execute(named_params! {
+                ":txid": Impl(tx.compute_txid()),
+                ":raw_tx": Impl(tx.as_ref().clone()),
+            })?;
+        }
+
+        let mut statement = db_tx
+            .prepare_cached(&format!(
+                "INSERT INTO {}(txid, last_seen) VALUES(:txid, :last_seen) ON CONFLICT(txid) DO UPDATE SET last_seen=MAX(IFNULL(last_seen, 0), :last_seen)",
+                Self::TXS_TABLE_NAME,
+            ))?;
+        for (&txid, &last_seen) in &self.last_seen {
+            let checked_time = last_seen.to_sql()?;
+            statement.execute(named_params! {
+                ":txid": Impl(txid),
+                ":last_seen": Some(checked_time),
+            })?;
+        }
+
+        let mut statement = db_tx.prepare_cached(&format!(
+            "REPLACE INTO {}(txid, vout, value, script) VALUES(:txid, :vout, :value, :script)",
+            Self::TXOUTS_TABLE_NAME,
+        ))?;
+        for (op, txo) in &self.txouts {
+            statement.execute(named_params! {
+                ":txid": Impl(op.txid),
+                ":vout": op.vout,
+                ":value": Impl(txo.value),
+                ":script": Impl(txo.script_pubkey.clone()),
+            })?;
+        }
+
+        let mut statement = db_tx.prepare_cached(&format!(
+            "REPLACE INTO {}(txid, block_height, block_hash, confirmation_time) VALUES(:txid, :block_height, :block_hash, :confirmation_time)",
+            Self::ANCHORS_TABLE_NAME,
+        ))?;
+        let mut statement_txid = db_tx.prepare_cached(&format!(
+            "INSERT OR IGNORE INTO {}(txid) VALUES(:txid)",
+            Self::TXS_TABLE_NAME,
+        ))?;
+        for (anchor, txid) in &self.anchors {
+            let anchor_block = anchor.anchor_block();
+            statement_txid.execute(named_params! {
+                ":txid": Impl(*txid)
+            })?;
+            statement.execute(named_params! {
+                ":txid": Impl(*txid),
+                ":block_height": anchor_block.height,
+                ":block_hash": Impl(anchor_block.hash),
+                ":confirmation_time": anchor.confirmation_time,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Persist `changeset` in batches of at most `max_rows` rows, committing each batch in its own
+    /// database transaction.
+    ///
+    /// Writing a huge changeset (e.g. after a full scan) in a single transaction can exhaust the
+    /// memory of constrained devices. Splitting the writes is safe for this changeset because it
+    /// is append-only: every batch [merges](Merge::merge) into what is stored, so the database
+    /// always holds a consistent, if incomplete, graph. Batches are written in the order
+    /// transactions, txouts, anchors, `last_seen`. If a batch fails, the batches before it stay
+    /// committed and persisting the whole changeset again completes it.
+    ///
+    /// This is not atomic, so don't use it where the changeset must be written together with
+    /// other changes. Changesets that remove data or must be applied as a whole, such as
+    /// [`local_chain::ChangeSet`] or the network and descriptors of a wallet, have no safe split
+    /// and only support [`Self::persist_to_sqlite`].
+    ///
+    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
+    pub fn persist_to_sqlite_in_batches(
+        &self,
+        conn: &mut rusqlite::Connection,
+        max_rows: usize,
+    ) -> rusqlite::Result<()> {
+        if max_rows == 0 {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+                Some("batches must have at least one row".to_string()),
+            ));
+        }
+        let mut batch = Self::default();
+        let mut batch_rows = 0;
+        let mut flush = |batch: &mut Self| -> rusqlite::Result<()> {
+            let db_tx = conn.transaction()?;
+            core::mem::take(batch).persist_to_sqlite(&db_tx)?;
+            db_tx.commit()
+        };
+        let rows = self
+            .txs
+            .iter()
+            .map(|tx| Self {
+                txs: [tx.clone()].into(),
+                ..Default::default()
+            })
+            .chain(self.txouts.iter().map(|(&op, txout)| Self {
+                txouts: [(op, txout.clone())].into(),
+                ..Default::default()
+            }))
+            .chain(self.anchors.iter().map(|anchor| Self {
+                anchors: [*anchor].into(),
+                ..Default::default()
+            }))
+            .chain(self.last_seen.iter().map(|(&txid, &last_seen)| Self {
+                last_seen: [(txid, last_seen)].into(),
+                ..Default::default()
+            }));
+        for row in rows {
+            batch.merge(row);
+            batch_rows += 1;
+            if batch_rows == max_rows {
+                flush(&mut batch)?;
+                batch_rows = 0;
+            }
+        }
+        if batch_rows > 0 {
+            flush(&mut batch)?;
+        }
+        Ok(())
+    }
+}
+
+impl local_chain::ChangeSet {
+    /// Schema name for the changeset.
+    pub const SCHEMA_NAME: &'static str = "bdk_localchain";
+    /// Latest version of the schema, which [`Self::init_sqlite_tables`] migrates to.
+    pub const SCHEMA_VERSION: u32 = 0;
+    /// Name of sqlite table that stores blocks of [`LocalChain`](local_chain::LocalChain).
+    pub const BLOCKS_TABLE_NAME: &'static str = "bdk_blocks";
+    /// Names of all schemas that [`Self::init_sqlite_tables`] migrates.
+    ///
+    /// Composite changesets concatenate these to check them with [`check_unique_schema_names`].
+    pub const SCHEMA_NAMES: &'static [&'static str] = &[Self::SCHEMA_NAME];
+    /// Names of all tables of the schema.
+    pub const TABLE_NAMES: &'static [&'static str] = &[Self::BLOCKS_TABLE_NAME];
+
+    /// Get v0 of sqlite [local_chain::ChangeSet] schema
+    pub fn schema_v0() -> String {
+        // blocks
+        format!(
+            "CREATE TABLE {} ( \
+            block_height INTEGER PRIMARY KEY NOT NULL, \
+            block_hash TEXT NOT NULL \
+            ) STRICT",
+            Self::BLOCKS_TABLE_NAME,
+        )
+    }
+
+    /// Initialize sqlite tables for persisting [`local_chain::LocalChain`].
+    pub fn init_sqlite_tables(db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+        migrate_schema(db_tx, Self::SCHEMA_NAME, &[&Self::schema_v0()])
+    }
+
+    /// Delete all rows of the sqlite tables while keeping the tables and their schema version.
+    pub fn clear_sqlite_tables(db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+        db_tx.execute(&format!("DELETE FROM {}", Self::BLOCKS_TABLE_NAME), ())?;
+        Ok(())
+    }
+
+    /// Construct a [`LocalChain`](local_chain::LocalChain) from sqlite database.
+    ///
+    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
+    pub fn from_sqlite(db_tx: &rusqlite::Transaction) -> rusqlite::Result<Self> {
+        let mut changeset = Self::default();
+
+        let mut statement = db_tx.prepare(&format!(
+            "SELECT block_height, block_hash FROM {}",
+            Self::BLOCKS_TABLE_NAME,
+        ))?;
+        let row_iter = statement.query_map([], |row| {
+            Ok((
+                get_sql::<u32>(row, "block_height")?,
+                get_sql::<Impl<bitcoin::BlockHash>>(row, "block_hash")?,
+            ))
+        })?;
+        for row in row_iter {
+            let (height, Impl(hash)) = row?;
+            changeset.blocks.insert(height, Some(hash));
+        }
+
+        Ok(changeset)
+    }
+
+    /// Persist `changeset` to the sqlite database.
+    ///
+    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
+    pub fn persist_to_sqlite(&self, db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+        let mut replace_statement = db_tx.prepare_cached(&format!(
+            "REPLACE INTO {}(block_height, block_hash) VALUES(:block_height, :block_hash)",
+            Self::BLOCKS_TABLE_NAME,
+        ))?;
+        let mut delete_statement = db_tx.prepare_cached(&format!(
+            "DELETE FROM {} WHERE block_height=:block_height",
+            Self::BLOCKS_TABLE_NAME,
+        ))?;
+        for (&height, &hash) in &self.blocks {
+            match hash {
+                Some(hash) => replace_statement.execute(named_params! {
+                    ":block_height": height,
+                    ":block_hash": Impl(hash),
+                })?,
+                None => delete_statement.execute(named_params! {
+                    ":block_height": height,
+                })?,
+            };
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "miniscript")]
+impl keychain_txout::ChangeSet {
+    /// Schema name for the changeset.
+    pub const SCHEMA_NAME: &'static str = "bdk_keychaintxout";
+    /// Latest version of the schema, which [`Self::init_sqlite_tables`] migrates to.
+    pub const SCHEMA_VERSION: u32 = 0;
+    /// Name for table that stores last revealed indices per descriptor id.
+    pub const LAST_REVEALED_TABLE_NAME: &'static str = "bdk_descriptor_last_revealed";
+    /// Names of all schemas that [`Self::init_sqlite_tables`] migrates.
+    ///
+    /// Composite changesets concatenate these to check them with [`check_unique_schema_names`].
+    pub const SCHEMA_NAMES: &'static [&'static str] = &[Self::SCHEMA_NAME];
+    /// Names of all tables of the schema.
+    pub const TABLE_NAMES: &'static [&'static str] = &[Self::LAST_REVEALED_TABLE_NAME];
+
+    /// Get v0 of sqlite [keychain_txout::ChangeSet] schema
+    pub fn schema_v0() -> String {
+        format!(
+            "CREATE TABLE {} ( \
+            descriptor_id TEXT PRIMARY KEY NOT NULL, \
+            last_revealed INTEGER NOT NULL \
+            ) STRICT",
+            Self::LAST_REVEALED_TABLE_NAME,
+        )
+    }
+
+    /// Initialize sqlite tables for persisting
+    /// [`KeychainTxOutIndex`](keychain_txout::KeychainTxOutIndex).
+    pub fn init_sqlite_tables(db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+        migrate_schema(db_tx, Self::SCHEMA_NAME, &[&Self::schema_v0()])
+    }
+
+    /// Delete all rows of the sqlite tables while keeping the tables and their schema version.
+    pub fn clear_sqlite_tables(db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+        db_tx.execute(
+            &format!("DELETE FROM {}", Self::LAST_REVEALED_TABLE_NAME),
+            (),
+        )?;
+        Ok(())
+    }
+
+    /// Construct [`KeychainTxOutIndex`](keychain_txout::KeychainTxOutIndex) from sqlite database
+    /// and given parameters.
+    ///
+    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
+    pub fn from_sqlite(db_tx: &rusqlite::Transaction) -> rusqlite::Result<Self> {
+        let mut changeset = Self::default();
+
+        let mut statement = db_tx.prepare(&format!(
+            "SELECT descriptor_id, last_revealed FROM {}",
+            Self::LAST_REVEALED_TABLE_NAME,
+        ))?;
+        let row_iter = statement.query_map([], |row| {
+            Ok((
+                get_sql::<Impl<DescriptorId>>(row, "descriptor_id")?,
+                get_sql::<u32>(row, "last_revealed")?,
+            ))
+        })?;
+        for row in row_iter {
+            let (Impl(descriptor_id), last_revealed) = row?;
+            changeset.last_revealed.insert(descriptor_id, last_revealed);
+        }
+
+        Ok(changeset)
+    }
+
+    /// Construct a partial changeset with only the rows of the given `descriptor_ids`.
+    ///
+    /// This avoids loading every keychain of a wallet with many of them when only a few are
+    /// needed. Ids are queried in chunks to stay below SQLite's limit on bound parameters, and
+    /// ids without a stored row are skipped. Merge the result into a changeset loaded with
+    /// [`Self::from_sqlite`] (or use it on its own) as needed.
+    ///
+    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
+    pub fn from_sqlite_for_descriptors(
+        db_tx: &rusqlite::Transaction,
+        descriptor_ids: &[DescriptorId],
+    ) -> rusqlite::Result<Self> {
+        /// Below `SQLITE_MAX_VARIABLE_NUMBER`, which is 999 for SQLite versions before 3.32.0.
+        const CHUNK_SIZE: usize = 500;
+        let mut changeset = Self::default();
+
+        for chunk in descriptor_ids.chunks(CHUNK_SIZE) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut statement = db_tx.prepare(&format!(
+                "SELECT descriptor_id, last_revealed FROM {} WHERE descriptor_id IN ({})",
+                Self::LAST_REVEALED_TABLE_NAME,
+                placeholders,
+            ))?;
+            let row_iter = statement.query_map(
+                rusqlite::params_from_iter(chunk.iter().map(|&id| Impl(id))),
+                |row| {
+                    Ok((
+                        get_sql::<Impl<DescriptorId>>(row, "descriptor_id")?,
+                        get_sql::<u32>(row, "last_revealed")?,
+                    ))
+                },
+            )?;
+            for row in row_iter {
+                let (Impl(descriptor_id), last_revealed) = row?;
+                changeset.last_revealed.insert(descriptor_id, last_revealed);
+            }
+        }
+
+        Ok(changeset)
+    }
+
+    /// Persist `changeset` to the sqlite database.
+    ///
+    /// Like [`Merge::merge`], a stored last revealed index is only replaced by a greater one.
+    ///
+    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
+    pub fn persist_to_sqlite(&self, db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+        let mut statement = db_tx.prepare_cached(&format!(
+            "INSERT INTO {}(descriptor_id, last_revealed) VALUES(:descriptor_id, :last_revealed) ON CONFLICT(descriptor_id) DO UPDATE SET last_revealed=MAX(last_revealed, :last_revealed)",
+            Self::LAST_REVEALED_TABLE_NAME,
+        ))?;
+        for (&descriptor_id, &last_revealed) in &self.last_revealed {
+            statement.execute(named_params! {
+                ":descriptor_id": Impl(descriptor_id),
+                ":last_revealed": last_revealed,
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use bdk_testenv::{anyhow, hash};
+    use bitcoin::{absolute, transaction, TxIn, TxOut};
+
+    #[test]
+    fn orphaned_txids_are_unanchored_and_stale() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        ChangeSet::init_sqlite_tables(&db_tx)?;
+
+        let new_tx = |lock_time| {
+            Arc::new(bitcoin::Transaction {
+                version: transaction::Version::TWO,
+                lock_time: absolute::LockTime::from_consensus(lock_time),
+                input: vec![TxIn::default()],
+                output: vec![],
+            })
+        };
+        let (confirmed, stale, recent, never_seen) = (new_tx(0), new_tx(1), new_tx(2), new_tx(3));
+        let anchor = ConfirmationBlockTime {
+            block_id: BlockId {
+                height: 21,
+                hash: hash!("anchor"),
+            },
+            confirmation_time: 1342,
+        };
+        ChangeSet {
+            txs: [&confirmed, &stale, &recent, &never_seen]
+                .into_iter()
+                .cloned()
+                .collect(),
+            anchors: [(anchor, confirmed.compute_txid())].into(),
+            last_seen: [
+                (confirmed.compute_txid(), 10),
+                (stale.compute_txid(), 10),
+                (recent.compute_txid(), 100),
+            ]
+            .into(),
+            ..Default::default()
+        }
+        .persist_to_sqlite(&db_tx)?;
+
+        let orphaned = ChangeSet::orphaned_txids(&db_tx, 50)?;
+        assert_eq!(
+            orphaned.into_iter().collect::<collections::BTreeSet<_>>(),
+            [stale.compute_txid(), never_seen.compute_txid()].into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn can_persist_anchors_and_txs_independently() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+
+        // init tables
+        {
+            let db_tx = conn.transaction()?;
+            ChangeSet::init_sqlite_tables(&db_tx)?;
+            db_tx.commit()?;
+        }
+
+        let tx = bitcoin::Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut::NULL],
+        };
+        let tx = Arc::new(tx);
+        let txid = tx.compute_txid();
+        let anchor = ConfirmationBlockTime {
+            block_id: BlockId {
+                height: 21,
+                hash: hash!("anchor"),
+            },
+            confirmation_time: 1342,
+        };
+
+        // First persist the anchor
+        {
+            let changeset = ChangeSet {
+                anchors: [(anchor, txid)].into(),
+                ..Default::default()
+            };
+            let db_tx = conn.transaction()?;
+            changeset.persist_to_sqlite(&db_tx)?;
+            db_tx.commit()?;
+        }
+
+        // Now persist the tx
+        {
+            let changeset = ChangeSet {
+                txs: [tx.clone()].into(),
+                ..Default::default()
+            };
+            let db_tx = conn.transaction()?;
+            changeset.persist_to_sqlite(&db_tx)?;
+            db_tx.commit()?;
+        }
+
+        // Loading changeset from sqlite should succeed
+        {
+            let db_tx = conn.transaction()?;
+            let changeset = ChangeSet::from_sqlite(&db_tx)?;
+            db_tx.commit()?;
+            assert!(changeset.txs.contains(&tx));
+            assert!(changeset.anchors.contains(&(anchor, txid)));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn anchored_txids_without_tx_lists_anchors_missing_their_tx() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        ChangeSet::init_sqlite_tables(&db_tx)?;
+
+        let tx = Arc::new(bitcoin::Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut::NULL],
+        });
+        let txid = tx.compute_txid();
+        let anchor = ConfirmationBlockTime {
+            block_id: BlockId {
+                height: 21,
+                hash: hash!("anchor"),
+            },
+            confirmation_time: 1342,
+        };
+        ChangeSet {
+            anchors: [(anchor, txid)].into(),
+            ..Default::default()
+        }
+        .persist_to_sqlite(&db_tx)?;
+        assert_eq!(ChangeSet::anchored_txids_without_tx(&db_tx)?, vec![txid]);
+
+        ChangeSet {
+            txs: [tx].into(),
+            ..Default::default()
+        }
+        .persist_to_sqlite(&db_tx)?;
+        assert!(ChangeSet::anchored_txids_without_tx(&db_tx)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn script_can_be_stored_as_hex() -> anyhow::Result<()> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let script = bitcoin::ScriptBuf::new_op_return([0xab_u8; 4]);
+
+        let stored: String =
+            conn.query_row("SELECT ?1", [SqlHex(script.clone())], |row| row.get(0))?;
+        assert_eq!(stored, "6a04abababab");
+
+        let SqlHex(loaded) = conn.query_row("SELECT ?1", [stored], |row| {
+            row.get::<_, SqlHex<bitcoin::ScriptBuf>>(0)
+        })?;
+        assert_eq!(loaded, script);
+        Ok(())
+    }
+
+    #[test]
+    fn keychain_txout_loads_subset_of_descriptors() -> anyhow::Result<()> {
+        use bitcoin::hashes::{sha256, Hash};
+        type ChangeSet = keychain_txout::ChangeSet;
+        let descriptor_ids = (0..1200_u32)
+            .map(|i| DescriptorId(sha256::Hash::hash(&i.to_le_bytes())))
+            .collect::<Vec<_>>();
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        ChangeSet::init_sqlite_tables(&db_tx)?;
+        ChangeSet {
+            last_revealed: descriptor_ids
+                .iter()
+                .step_by(2)
+                .zip(0..)
+                .map(|(&id, last_revealed)| (id, last_revealed))
+                .collect(),
+        }
+        .persist_to_sqlite(&db_tx)?;
+
+        // spans several chunks, and half of the ids have no stored row
+        let requested = &descriptor_ids[100..1150];
+        let partial = ChangeSet::from_sqlite_for_descriptors(&db_tx, requested)?;
+        let full = ChangeSet::from_sqlite(&db_tx)?;
+        assert_eq!(partial.last_revealed.len(), requested.len() / 2);
+        assert_eq!(
+            partial.last_revealed,
+            full.last_revealed
+                .into_iter()
+                .filter(|(id, _)| requested.contains(id))
+                .collect()
+        );
+        assert_eq!(
+            ChangeSet::from_sqlite_for_descriptors(&db_tx, &[])?,
+            ChangeSet::default()
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_blob_round_trips_merged_changeset() -> anyhow::Result<()> {
+        type ChangeSet = keychain_txout::ChangeSet;
+        const TABLE_NAME: &str = "test_json_blob";
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        JsonBlob::<ChangeSet>::init_sqlite_tables(&db_tx, TABLE_NAME)?;
+        assert_eq!(
+            JsonBlob::<ChangeSet>::from_sqlite(&db_tx, TABLE_NAME)?,
+            JsonBlob::default()
+        );
+
+        let first = ChangeSet {
+            last_revealed: [(DescriptorId(hash!("a")), 3)].into(),
+        };
+        let second = ChangeSet {
+            last_revealed: [(DescriptorId(hash!("b")), 1)].into(),
+        };
+        JsonBlob(first.clone()).persist_to_sqlite(&db_tx, TABLE_NAME)?;
+        JsonBlob(second.clone()).persist_to_sqlite(&db_tx, TABLE_NAME)?;
+
+        let mut expected = first;
+        expected.merge(second);
+        assert_eq!(
+            JsonBlob::<ChangeSet>::from_sqlite(&db_tx, TABLE_NAME)?,
+            JsonBlob(expected)
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn merged_row_merges_with_stored_changeset() -> anyhow::Result<()> {
+        type ChangeSet = local_chain::ChangeSet;
+        const TABLE_NAME: &str = "test_merged_rows";
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        migrate_schema(
+            &db_tx,
+            "test_merged_rows",
+            &[&merged_row_table_schema(TABLE_NAME)?],
+        )?;
+        assert_eq!(load_merged_row::<ChangeSet>(&db_tx, TABLE_NAME, "a")?, None);
+
+        let first = ChangeSet::from([(0, Some(hash!("0"))), (1, Some(hash!("1")))]);
+        let second = ChangeSet::from([(1, None), (2, Some(hash!("2")))]);
+        persist_merged_row(&db_tx, TABLE_NAME, "a", &first)?;
+        persist_merged_row(&db_tx, TABLE_NAME, "a", &second)?;
+
+        let mut expected = first;
+        expected.merge(second);
+        assert_eq!(
+            load_merged_row::<ChangeSet>(&db_tx, TABLE_NAME, "a")?,
+            Some(expected)
+        );
+        assert_eq!(load_merged_row::<ChangeSet>(&db_tx, TABLE_NAME, "b")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn block_header_blob_must_be_80_bytes() -> anyhow::Result<()> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let header = bitcoin::constants::genesis_block(bitcoin::Network::Regtest).header;
+
+        let Impl(loaded) = conn.query_row("SELECT ?1", [Impl(header)], |row| {
+            row.get::<_, Impl<bitcoin::block::Header>>(0)
+        })?;
+        assert_eq!(loaded, header);
+
+        let res = conn.query_row("SELECT ?1", [vec![0_u8; 81]], |row| {
+            row.get::<_, Impl<bitcoin::block::Header>>(0)
+        });
+        assert!(matches!(
+            res,
+            Err(rusqlite::Error::FromSqlConversionFailure(..))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn schema_at_least_reports_applied_versions() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+
+        // uninitialized database
+        assert!(!schema_at_least(&db_tx, ChangeSet::SCHEMA_NAME, 0)?);
+
+        migrate_schema(&db_tx, ChangeSet::SCHEMA_NAME, &[&ChangeSet::schema_v0()])?;
+        assert!(schema_at_least(&db_tx, ChangeSet::SCHEMA_NAME, 0)?);
+        assert!(!schema_at_least(&db_tx, ChangeSet::SCHEMA_NAME, 1)?);
+        assert!(!schema_at_least(&db_tx, "unknown_schema", 0)?);
+
+        ChangeSet::init_sqlite_tables(&db_tx)?;
+        assert!(schema_at_least(&db_tx, ChangeSet::SCHEMA_NAME, 1)?);
+        Ok(())
+    }
+
+    #[test]
+    fn keyed_table_enforces_unique_keys() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        let ddl = create_keyed_table(
+            "test_keyed",
+            "descriptor_id",
+            "TEXT",
+            &["last_revealed INTEGER NOT NULL"],
+        )?;
+        migrate_schema(&db_tx, "test_keyed", &[&ddl])?;
+
+        let insert = "INSERT INTO test_keyed(descriptor_id, last_revealed) VALUES('a', 1)";
+        db_tx.execute(insert, ())?;
+        assert_eq!(
+            db_tx
+                .execute(insert, ())
+                .expect_err("duplicate key must be rejected")
+                .sqlite_error_code(),
+            Some(rusqlite::ErrorCode::ConstraintViolation)
+        );
+        // there is no rowid to select
+        assert!(db_tx.prepare("SELECT rowid FROM test_keyed").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn composite_keyed_table_upserts_anchor_rows() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        let ddl = create_composite_keyed_table(
+            "test_anchors",
+            &[("txid", "TEXT"), ("block_hash", "TEXT")],
+            &["confirmation_time INTEGER NOT NULL"],
+        )?;
+        assert_eq!(
+            ddl,
+            "CREATE TABLE test_anchors ( txid TEXT NOT NULL, block_hash TEXT NOT NULL, \
+            confirmation_time INTEGER NOT NULL, PRIMARY KEY (txid, block_hash) ) WITHOUT ROWID, STRICT"
+        );
+        migrate_schema(&db_tx, "test_anchors", &[&ddl])?;
+
+        let sql = upsert_sql(
+            "test_anchors",
+            &["txid", "block_hash"],
+            &["confirmation_time"],
+        )?;
+        let txid = Impl::<bitcoin::Txid>(hash!("tx"));
+        let (hash_a, hash_b): (bitcoin::BlockHash, bitcoin::BlockHash) = (hash!("a"), hash!("b"));
+        for (block_hash, confirmation_time) in [(hash_a, 1), (hash_b, 2), (hash_a, 3)] {
+            db_tx.execute(
+                &sql,
+                named_params! {
+                    ":txid": txid,
+                    ":block_hash": Impl(block_hash),
+                    ":confirmation_time": confirmation_time,
+                },
+            )?;
+        }
+        let mut confirmation_times = db_tx
+            .prepare("SELECT confirmation_time FROM test_anchors")?
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        confirmation_times.sort();
+        assert_eq!(confirmation_times, vec![2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn column_helpers_migrate_populated_table() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        db_tx.execute_batch(
+            "CREATE TABLE test_columns ( id INTEGER PRIMARY KEY, height INTEGER ) STRICT; \
+            INSERT INTO test_columns(height) VALUES (1), (2), (NULL)",
+        )?;
+
+        add_column_with_default(&db_tx, "test_columns", "block_height", "INTEGER", "-1")?;
+        assert!(
+            add_column_with_default(&db_tx, "test_columns", "block_height", "INTEGER", "-1")
+                .is_err()
+        );
+        add_column_with_default(
+            &db_tx,
+            "test_columns",
+            "status",
+            "TEXT NOT NULL",
+            "'unknown'",
+        )?;
+        assert_eq!(
+            copy_column(&db_tx, "test_columns", "height", "block_height")?,
+            3
+        );
+        assert!(copy_column(&db_tx, "test_columns", "no_such_column", "block_height").is_err());
+        assert!(copy_column(&db_tx, "test_columns", "height", "no_such_column").is_err());
+
+        let rows = db_tx
+            .prepare("SELECT block_height, status FROM test_columns ORDER BY id")?
+            .query_map([], |row| {
+                Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let unknown = || "unknown".to_string();
+        assert_eq!(
+            rows,
+            vec![
+                (Some(1), unknown()),
+                (Some(2), unknown()),
+                (None, unknown())
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn backfill_in_batches_resumes_after_interruption() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            "CREATE TABLE test_backfill ( value INTEGER NOT NULL, doubled INTEGER ) STRICT; \
+            INSERT INTO test_backfill(value) VALUES (1), (2), (3), (4), (5), (6), (7), (8)",
+        )?;
+        let batch = |db_tx: &Transaction, cursor: Option<i64>| {
+            let rowids = db_tx
+                .prepare("SELECT rowid FROM test_backfill WHERE rowid > ?1 ORDER BY rowid LIMIT 3")?
+                .query_map([cursor.unwrap_or(0)], |row| row.get::<_, i64>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            for rowid in &rowids {
+                db_tx.execute(
+                    "UPDATE test_backfill SET doubled=value*2 WHERE rowid=?1",
+                    [rowid],
+                )?;
+            }
+            Ok(rowids.last().copied())
+        };
+
+        // the second batch is interrupted after writing, so only the first one is kept
+        let mut calls = 0;
+        let res = backfill_in_batches(&mut conn, "test", |db_tx, cursor| {
+            let next_cursor = batch(db_tx, cursor)?;
+            calls += 1;
+            if calls == 2 {
+                return Err(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_INTERRUPT),
+                    None,
+                ));
+            }
+            Ok(next_cursor)
+        });
+        assert!(res.is_err());
+        let backfilled: u32 = conn.query_row(
+            "SELECT COUNT(*) FROM test_backfill WHERE doubled IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(backfilled, 3);
+
+        // batches of rows 4-6 and 7-8, then an empty batch that completes the backfill
+        let mut cursors = Vec::new();
+        let committed = backfill_in_batches(&mut conn, "test", |db_tx, cursor| {
+            cursors.push(cursor);
+            batch(db_tx, cursor)
+        })?;
+        assert_eq!(committed, 3);
+        assert_eq!(cursors, vec![Some(3), Some(6), Some(8)]);
+        assert_eq!(
+            backfill_in_batches(&mut conn, "test", |_, _| panic!("backfill is complete"))?,
+            0
+        );
+        let mismatched: u32 = conn.query_row(
+            "SELECT COUNT(*) FROM test_backfill WHERE doubled IS NOT value*2",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(mismatched, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn dedupe_table_keeps_last_row_per_key() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        db_tx.execute_batch(
+            "CREATE TABLE test_legacy ( txid TEXT, vout INTEGER, value INTEGER ); \
+            INSERT INTO test_legacy VALUES ('a', 0, 1), ('a', 0, 2), ('a', 1, 3), \
+            ('b', 0, 4), ('a', 0, 5), ('b', 0, 6)",
+        )?;
+        assert!(dedupe_table(&db_tx, "test_legacy", &[]).is_err());
+
+        assert_eq!(dedupe_table(&db_tx, "test_legacy", &["txid", "vout"])?, 3);
+        assert_eq!(dedupe_table(&db_tx, "test_legacy", &["txid", "vout"])?, 0);
+        let rows = db_tx
+            .prepare("SELECT txid, vout, value FROM test_legacy ORDER BY txid, vout")?
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, u32>(1)?,
+                    row.get::<_, u32>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            rows,
+            vec![
+                ("a".to_string(), 0, 5),
+                ("a".to_string(), 1, 3),
+                ("b".to_string(), 0, 6)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn identifiers_with_sql_syntax_are_rejected() -> anyhow::Result<()> {
+        for name in ["bdk_txs", "Wallet_1", "_"] {
+            assert_eq!(validate_identifier(name), Ok(()));
+        }
+        for name in [
+            "",
+            "bdk_txs\"",
+            "bdk_txs'",
+            "bdk_txs; DROP TABLE bdk_schemas",
+            "bdk txs",
+            "bdk_txs--",
+        ] {
+            let err = validate_identifier(name).expect_err("must reject");
+            assert_eq!(err.identifier(), name);
+        }
+
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        db_tx.execute_batch("CREATE TABLE test_names ( a INTEGER, b INTEGER ) STRICT")?;
+        let injected = "test_names; DROP TABLE test_names";
+        let err = copy_column(&db_tx, injected, "a", "b").expect_err("must reject table name");
+        assert!(matches!(
+            err,
+            rusqlite::Error::ToSqlConversionFailure(err) if err.is::<InvalidIdentifier>()
+        ));
+        assert!(
+            add_column_with_default(&db_tx, "test_names", "c INTEGER, d", "INTEGER", "0").is_err()
         );
-        let drop_anchor_column = format!(
-            "ALTER TABLE {} DROP COLUMN anchor",
-            Self::ANCHORS_TABLE_NAME,
+        assert!(upsert_sql(injected, &["a"], &["b"]).is_err());
+        assert!(upsert_sql("test_names", &["a"], &["b=1, a"]).is_err());
+        assert!(compat_view_script(injected, "SELECT a FROM test_names").is_err());
+        assert!(create_keyed_table(injected, "a", "INTEGER", &[]).is_err());
+        assert!(create_keyed_table("t", "a", "INTEGER) STRICT; --", &[]).is_err());
+        assert!(create_composite_keyed_table(injected, &[("a", "INTEGER")], &[]).is_err());
+        assert!(create_composite_keyed_table("t", &[("a, b", "INTEGER")], &[]).is_err());
+        assert!(timestamps_table_schema(injected).is_err());
+        #[cfg(feature = "json")]
+        {
+            assert!(merged_row_table_schema(injected).is_err());
+            assert!(SerdeBlob::<keychain_txout::ChangeSet, Json>::schema_v0(injected).is_err());
+            assert!(load_merged_row::<collections::BTreeSet<u32>>(&db_tx, injected, "id").is_err());
+            let injected_ddl = "t(x TEXT); DROP TABLE test_names; --";
+            assert!(JsonBlob::<keychain_txout::ChangeSet>::init_sqlite_tables(
+                &db_tx,
+                injected_ddl
+            )
+            .is_err());
+            assert!(
+                SerdeBlob::<keychain_txout::ChangeSet, Json>::init_sqlite_tables(
+                    &db_tx,
+                    injected_ddl
+                )
+                .is_err()
+            );
+        }
+        assert!(table_exists(&db_tx, "test_names")?);
+        Ok(())
+    }
+
+    #[test]
+    fn upsert_sql_updates_existing_rows() -> anyhow::Result<()> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute(
+            "CREATE TABLE test_upsert (txid TEXT NOT NULL, vout INTEGER NOT NULL, value INTEGER, \
+            PRIMARY KEY (txid, vout)) STRICT",
+            (),
+        )?;
+        let sql = upsert_sql("test_upsert", &["txid", "vout"], &["value"])?;
+        assert_eq!(
+            sql,
+            "INSERT INTO test_upsert(txid, vout, value) VALUES(:txid, :vout, :value) \
+            ON CONFLICT(txid, vout) DO UPDATE SET value=:value"
         );
-        format!("{add_confirmation_time_column}; {extract_confirmation_time_from_anchor_column}; {drop_anchor_column}")
+        let txid = Impl::<bitcoin::Txid>(hash!("tx"));
+        for value in [1, 2] {
+            conn.execute(
+                &sql,
+                named_params! { ":txid": txid, ":vout": 0, ":value": value },
+            )?;
+        }
+        let values = conn
+            .prepare("SELECT value FROM test_upsert")?
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(values, vec![2]);
+
+        let sql = upsert_sql("test_upsert", &["txid", "vout"], &[])?;
+        conn.execute(&sql, named_params! { ":txid": txid, ":vout": 0 })?;
+        Ok(())
     }
 
-    /// Initialize sqlite tables.
-    pub fn init_sqlite_tables(db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
-        migrate_schema(
-            db_tx,
-            Self::SCHEMA_NAME,
-            &[&Self::schema_v0(), &Self::schema_v1()],
-        )
+    #[test]
+    fn duplicate_schema_names_are_rejected() {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let names = [
+            ChangeSet::SCHEMA_NAMES,
+            local_chain::ChangeSet::SCHEMA_NAMES,
+            keychain_txout::ChangeSet::SCHEMA_NAMES,
+        ]
+        .concat();
+        assert!(check_unique_schema_names(&names).is_ok());
+
+        let err = check_unique_schema_names(&[
+            ChangeSet::SCHEMA_NAME,
+            local_chain::ChangeSet::SCHEMA_NAME,
+            ChangeSet::SCHEMA_NAME,
+        ])
+        .expect_err("duplicate must be rejected");
+        assert!(err.to_string().contains(ChangeSet::SCHEMA_NAME));
     }
 
-    /// Construct a [`TxGraph`] from an sqlite database.
-    ///
-    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
-    pub fn from_sqlite(db_tx: &rusqlite::Transaction) -> rusqlite::Result<Self> {
-        let mut changeset = Self::default();
+    #[test]
+    fn persist_in_batches_writes_whole_changeset() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let txs = (0..3_u32)
+            .map(|i| {
+                Arc::new(bitcoin::Transaction {
+                    version: transaction::Version::TWO,
+                    lock_time: absolute::LockTime::from_consensus(i),
+                    input: vec![TxIn::default()],
+                    output: vec![],
+                })
+            })
+            .collect::<Vec<_>>();
+        let anchor = ConfirmationBlockTime {
+            block_id: BlockId {
+                height: 10,
+                hash: hash!("B"),
+            },
+            confirmation_time: 100,
+        };
+        let changeset = ChangeSet {
+            txs: txs.iter().cloned().collect(),
+            txouts: [(
+                bitcoin::OutPoint::new(hash!("floating"), 0),
+                TxOut {
+                    value: bitcoin::Amount::from_sat(1),
+                    script_pubkey: bitcoin::ScriptBuf::new(),
+                },
+            )]
+            .into(),
+            anchors: [(anchor, txs[0].compute_txid())].into(),
+            last_seen: txs.iter().map(|tx| (tx.compute_txid(), 200)).collect(),
+        };
 
-        let mut statement = db_tx.prepare(&format!(
-            "SELECT txid, raw_tx, last_seen FROM {}",
-            Self::TXS_TABLE_NAME,
-        ))?;
-        let row_iter = statement.query_map([], |row| {
-            Ok((
-                row.get::<_, Impl<bitcoin::Txid>>("txid")?,
-                row.get::<_, Option<Impl<bitcoin::Transaction>>>("raw_tx")?,
-                row.get::<_, Option<u64>>("last_seen")?,
-            ))
-        })?;
-        for row in row_iter {
-            let (Impl(txid), tx, last_seen) = row?;
-            if let Some(Impl(tx)) = tx {
-                changeset.txs.insert(Arc::new(tx));
-            }
-            if let Some(last_seen) = last_seen {
-                changeset.last_seen.insert(txid, last_seen);
-            }
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        {
+            let db_tx = conn.transaction()?;
+            ChangeSet::init_sqlite_tables(&db_tx)?;
+            db_tx.commit()?;
         }
+        assert!(changeset
+            .persist_to_sqlite_in_batches(&mut conn, 0)
+            .is_err());
+        changeset.persist_to_sqlite_in_batches(&mut conn, 2)?;
 
-        let mut statement = db_tx.prepare(&format!(
-            "SELECT txid, vout, value, script FROM {}",
-            Self::TXOUTS_TABLE_NAME,
+        let db_tx = conn.transaction()?;
+        assert_eq!(ChangeSet::from_sqlite(&db_tx)?, changeset);
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_schemas_table_is_normalized() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        verify_schemas_table(&db_tx)?;
+
+        // a legacy table without constraints
+        db_tx.execute_batch(&format!(
+            "CREATE TABLE {table}( name TEXT, version INTEGER ); \
+            INSERT INTO {table}(name, version) VALUES \
+            ('a', 0), ('a', 2), ('b', NULL), ('c', -1), ('d', 1), (NULL, 3)",
+            table = SCHEMAS_TABLE_NAME,
         ))?;
-        let row_iter = statement.query_map([], |row| {
-            Ok((
-                row.get::<_, Impl<bitcoin::Txid>>("txid")?,
-                row.get::<_, u32>("vout")?,
-                row.get::<_, Impl<bitcoin::Amount>>("value")?,
-                row.get::<_, Impl<bitcoin::ScriptBuf>>("script")?,
-            ))
-        })?;
-        for row in row_iter {
-            let (Impl(txid), vout, Impl(value), Impl(script_pubkey)) = row?;
-            changeset.txouts.insert(
-                bitcoin::OutPoint { txid, vout },
-                bitcoin::TxOut {
-                    value,
-                    script_pubkey,
-                },
+        let err = verify_schemas_table(&db_tx).expect_err("table is malformed");
+        let message = err.to_string();
+        for problem in [
+            "not STRICT",
+            "unexpected columns",
+            "duplicate schema names [Some(\"a\")]",
+            "Some(\"b\"), Some(\"c\")",
+        ] {
+            assert!(message.contains(problem), "{}", message);
+        }
+
+        normalize_schemas_table(&db_tx)?;
+        verify_schemas_table(&db_tx)?;
+        let versions = ["a", "b", "c", "d"]
+            .iter()
+            .map(|name| stored_schema_version(&db_tx, name))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        assert_eq!(versions, vec![Some(2), None, None, Some(1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn init_migrates_to_latest_schema_version() -> anyhow::Result<()> {
+        type TxGraphChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        assert_eq!(
+            stored_schema_version(&db_tx, TxGraphChangeSet::SCHEMA_NAME)?,
+            None
+        );
+
+        TxGraphChangeSet::init_sqlite_tables(&db_tx)?;
+        local_chain::ChangeSet::init_sqlite_tables(&db_tx)?;
+        keychain_txout::ChangeSet::init_sqlite_tables(&db_tx)?;
+        for (schema_name, latest_version) in [
+            (
+                TxGraphChangeSet::SCHEMA_NAME,
+                TxGraphChangeSet::SCHEMA_VERSION,
+            ),
+            (
+                local_chain::ChangeSet::SCHEMA_NAME,
+                local_chain::ChangeSet::SCHEMA_VERSION,
+            ),
+            (
+                keychain_txout::ChangeSet::SCHEMA_NAME,
+                keychain_txout::ChangeSet::SCHEMA_VERSION,
+            ),
+        ] {
+            assert_eq!(
+                stored_schema_version(&db_tx, schema_name)?,
+                Some(latest_version),
+                "{}",
+                schema_name
             );
         }
+        Ok(())
+    }
 
-        let mut statement = db_tx.prepare(&format!(
-            "SELECT block_hash, block_height, confirmation_time, txid FROM {}",
-            Self::ANCHORS_TABLE_NAME,
-        ))?;
-        let row_iter = statement.query_map([], |row| {
-            Ok((
-                row.get::<_, Impl<bitcoin::BlockHash>>("block_hash")?,
-                row.get::<_, u32>("block_height")?,
-                row.get::<_, u64>("confirmation_time")?,
-                row.get::<_, Impl<bitcoin::Txid>>("txid")?,
-            ))
-        })?;
-        for row in row_iter {
-            let (hash, height, confirmation_time, Impl(txid)) = row?;
-            changeset.anchors.insert((
-                ConfirmationBlockTime {
-                    block_id: BlockId::from((&height, &hash.0)),
-                    confirmation_time,
-                },
-                txid,
-            ));
+    #[test]
+    fn migration_plan_rejects_schema_added_twice() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        let (v0, other_v0) = ("CREATE TABLE a (x INTEGER)", "CREATE TABLE b (x INTEGER)");
+        let mut plan = MigrationPlan::default();
+        plan.add_schema(&db_tx, "test_shared_name", &[v0])?;
+        assert!(plan
+            .add_schema(&db_tx, "test_shared_name", &[other_v0])
+            .is_err());
+        // the rejected schema must not keep other schemas from being added
+        plan.add_schema(&db_tx, "test_other_name", &[other_v0])?;
+        assert_eq!(
+            plan.steps()
+                .iter()
+                .map(|step| step.schema_name)
+                .collect::<Vec<_>>(),
+            vec!["test_shared_name", "test_other_name"]
+        );
+        plan.execute(&db_tx, |_, _| {})?;
+        assert!(table_exists(&db_tx, "a")? && table_exists(&db_tx, "b")?);
+        Ok(())
+    }
+
+    #[test]
+    fn migrating_a_schema_name_with_different_scripts_fails() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        let (v0, v1) = (
+            "CREATE TABLE a (x INTEGER)",
+            "ALTER TABLE a ADD COLUMN y INTEGER",
+        );
+        migrate_schema(&db_tx, "test_shared_name", &[v0])?;
+        // the same component migrating again, with a newer version appended
+        migrate_schema(&db_tx, "test_shared_name", &[v0])?;
+        migrate_schema(&db_tx, "test_shared_name", &[v0, v1])?;
+
+        // another component using the same name
+        assert!(
+            migrate_schema(&db_tx, "test_shared_name", &["CREATE TABLE b (x INTEGER)"]).is_err()
+        );
+        assert!(!table_exists(&db_tx, "b")?);
+        assert_eq!(schema_version(&db_tx, "test_shared_name")?, Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn migration_plan_reports_progress() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        migrate_schema(&db_tx, ChangeSet::SCHEMA_NAME, &[&ChangeSet::schema_v0()])?;
+
+        let (txgraph_v0, txgraph_v1) = (ChangeSet::schema_v0(), ChangeSet::schema_v1());
+        let localchain_v0 = local_chain::ChangeSet::schema_v0();
+        let mut plan = MigrationPlan::default();
+        plan.add_schema(&db_tx, ChangeSet::SCHEMA_NAME, &[&txgraph_v0, &txgraph_v1])?;
+        plan.add_schema(
+            &db_tx,
+            local_chain::ChangeSet::SCHEMA_NAME,
+            &[&localchain_v0],
+        )?;
+        assert_eq!(
+            plan.steps()
+                .iter()
+                .map(|step| (step.schema_name, step.version, step.statement_count))
+                .collect::<Vec<_>>(),
+            vec![
+                (ChangeSet::SCHEMA_NAME, 1, 3),
+                (local_chain::ChangeSet::SCHEMA_NAME, 0, 1),
+            ],
+        );
+
+        let mut completed = Vec::new();
+        plan.execute(&db_tx, |n, step| completed.push((n, step.schema_name)))?;
+        assert_eq!(
+            completed,
+            vec![
+                (1, ChangeSet::SCHEMA_NAME),
+                (2, local_chain::ChangeSet::SCHEMA_NAME)
+            ]
+        );
+
+        let mut plan = MigrationPlan::default();
+        plan.add_schema(&db_tx, ChangeSet::SCHEMA_NAME, &[&txgraph_v0, &txgraph_v1])?;
+        assert!(plan.is_empty(), "all versions are applied");
+        Ok(())
+    }
+
+    #[test]
+    fn clearing_tables_keeps_schema() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        ChangeSet::init_sqlite_tables(&db_tx)?;
+
+        let tx = Arc::new(bitcoin::Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut::NULL],
+        });
+        let anchor = ConfirmationBlockTime {
+            block_id: BlockId {
+                height: 21,
+                hash: hash!("anchor"),
+            },
+            confirmation_time: 1342,
+        };
+        ChangeSet {
+            txs: [tx.clone()].into(),
+            anchors: [(anchor, tx.compute_txid())].into(),
+            last_seen: [(tx.compute_txid(), 100)].into(),
+            ..Default::default()
         }
+        .persist_to_sqlite(&db_tx)?;
 
-        Ok(changeset)
+        ChangeSet::clear_sqlite_tables(&db_tx)?;
+        assert_eq!(ChangeSet::from_sqlite(&db_tx)?, ChangeSet::default());
+        assert!(schema_at_least(&db_tx, ChangeSet::SCHEMA_NAME, 1)?);
+        Ok(())
     }
 
-    /// Persist `changeset` to the sqlite database.
-    ///
-    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
-    pub fn persist_to_sqlite(&self, db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
-        let mut statement = db_tx.prepare_cached(&format!(
-            "INSERT INTO {}(txid, raw_tx) VALUES(:txid, :raw_tx) ON CONFLICT(txid) DO UPDATE SET raw_tx=:raw_tx",
-            Self::TXS_TABLE_NAME,
-        ))?;
-        for tx in &self.txs {
-            statement.execute(named_params! {
-                ":txid": Impl(tx.compute_txid()),
-                ":raw_tx": Impl(tx.as_ref().clone()),
-            })?;
+    #[test]
+    fn compat_view_survives_layout_change() -> anyhow::Result<()> {
+        const SCHEMA_NAME: &str = "test_compat_view";
+        let v0 = format!(
+            "CREATE TABLE test_notes ( txid TEXT PRIMARY KEY NOT NULL, note TEXT NOT NULL ) STRICT; {}",
+            compat_view_script("test_notes_v1", "SELECT txid, note FROM test_notes")?,
+        );
+        let v1 = format!(
+            "ALTER TABLE test_notes RENAME COLUMN note TO memo; {}",
+            compat_view_script("test_notes_v1", "SELECT txid, memo AS note FROM test_notes")?,
+        );
+        let load = |db_tx: &Transaction| -> rusqlite::Result<Vec<(String, String)>> {
+            db_tx
+                .prepare("SELECT txid, note FROM test_notes_v1")?
+                .query_map([], |row| Ok((row.get("txid")?, row.get("note")?)))?
+                .collect()
+        };
+
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        migrate_schema(&db_tx, SCHEMA_NAME, &[&v0])?;
+        db_tx.execute("INSERT INTO test_notes(txid, note) VALUES('a', 'rent')", ())?;
+        let before = load(&db_tx)?;
+
+        migrate_schema(&db_tx, SCHEMA_NAME, &[&v0, &v1])?;
+        assert_eq!(load(&db_tx)?, before);
+        Ok(())
+    }
+
+    #[test]
+    fn loaders_ignore_unknown_columns() -> anyhow::Result<()> {
+        type TxGraphChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        TxGraphChangeSet::init_sqlite_tables(&db_tx)?;
+        local_chain::ChangeSet::init_sqlite_tables(&db_tx)?;
+        keychain_txout::ChangeSet::init_sqlite_tables(&db_tx)?;
+        let tx_graph_changeset = TxGraphChangeSet {
+            last_seen: [(hash!("tx"), 100)].into(),
+            ..Default::default()
+        };
+        let local_chain_changeset = local_chain::ChangeSet {
+            blocks: [(0, Some(hash!("genesis")))].into(),
+        };
+        let keychain_txout_changeset = keychain_txout::ChangeSet {
+            last_revealed: [(DescriptorId(hash!("descriptor")), 7)].into(),
+        };
+        tx_graph_changeset.persist_to_sqlite(&db_tx)?;
+        local_chain_changeset.persist_to_sqlite(&db_tx)?;
+        keychain_txout_changeset.persist_to_sqlite(&db_tx)?;
+
+        // columns that a newer version might add
+        for table_name in TxGraphChangeSet::TABLE_NAMES
+            .iter()
+            .chain(local_chain::ChangeSet::TABLE_NAMES)
+            .chain(keychain_txout::ChangeSet::TABLE_NAMES)
+        {
+            db_tx.execute(
+                &format!("ALTER TABLE {} ADD COLUMN future_note TEXT", table_name),
+                (),
+            )?;
         }
+        assert_eq!(TxGraphChangeSet::from_sqlite(&db_tx)?, tx_graph_changeset);
+        assert_eq!(
+            local_chain::ChangeSet::from_sqlite(&db_tx)?,
+            local_chain_changeset
+        );
+        assert_eq!(
+            keychain_txout::ChangeSet::from_sqlite(&db_tx)?,
+            keychain_txout_changeset
+        );
+        Ok(())
+    }
 
-        let mut statement = db_tx
-            .prepare_cached(&format!(
-                "INSERT INTO {}(txid, last_seen) VALUES(:txid, :last_seen) ON CONFLICT(txid) DO UPDATE SET last_seen=:last_seen",
-                Self::TXS_TABLE_NAME,
-            ))?;
-        for (&txid, &last_seen) in &self.last_seen {
-            let checked_time = last_seen.to_sql()?;
-            statement.execute(named_params! {
-                ":txid": Impl(txid),
-                ":last_seen": Some(checked_time),
-            })?;
+    #[test]
+    fn cached_statements_see_migrated_schema() -> anyhow::Result<()> {
+        const SCHEMA_NAME: &str = "test_statement_cache";
+        const SELECT: &str = "SELECT * FROM test_notes";
+        let v0 = "CREATE TABLE test_notes ( txid TEXT PRIMARY KEY NOT NULL ) STRICT";
+        let v1 = "ALTER TABLE test_notes ADD COLUMN note TEXT";
+
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        migrate_schema(&db_tx, SCHEMA_NAME, &[v0])?;
+        db_tx.execute("INSERT INTO test_notes(txid) VALUES('a')", ())?;
+        assert_eq!(db_tx.prepare_cached(SELECT)?.column_count(), 1);
+
+        migrate_schema(&db_tx, SCHEMA_NAME, &[v0, v1])?;
+        db_tx.execute("UPDATE test_notes SET note = 'rent'", ())?;
+        let mut statement = db_tx.prepare_cached(SELECT)?;
+        assert_eq!(statement.column_count(), 2);
+        let note = statement.query_row([], |row| row.get::<_, String>("note"))?;
+        assert_eq!(note, "rent");
+        Ok(())
+    }
+
+    #[test]
+    fn persisting_follows_merge_semantics() -> anyhow::Result<()> {
+        type TxGraphChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        TxGraphChangeSet::init_sqlite_tables(&db_tx)?;
+        keychain_txout::ChangeSet::init_sqlite_tables(&db_tx)?;
+
+        let txid = hash!("tx");
+        let descriptor_id = DescriptorId(hash!("descriptor"));
+        let newer = (
+            TxGraphChangeSet {
+                last_seen: [(txid, 200)].into(),
+                ..Default::default()
+            },
+            keychain_txout::ChangeSet {
+                last_revealed: [(descriptor_id, 5)].into(),
+            },
+        );
+        let older = (
+            TxGraphChangeSet {
+                last_seen: [(txid, 100)].into(),
+                ..Default::default()
+            },
+            keychain_txout::ChangeSet {
+                last_revealed: [(descriptor_id, 3)].into(),
+            },
+        );
+        for (tx_graph, indexer) in [&newer, &older] {
+            tx_graph.persist_to_sqlite(&db_tx)?;
+            indexer.persist_to_sqlite(&db_tx)?;
         }
 
-        let mut statement = db_tx.prepare_cached(&format!(
-            "REPLACE INTO {}(txid, vout, value, script) VALUES(:txid, :vout, :value, :script)",
-            Self::TXOUTS_TABLE_NAME,
-        ))?;
-        for (op, txo) in &self.txouts {
-            statement.execute(named_params! {
-                ":txid": Impl(op.txid),
-                ":vout": op.vout,
-                ":value": Impl(txo.value),
-                ":script": Impl(txo.script_pubkey.clone()),
-            })?;
+        let mut expected = newer.clone();
+        expected.merge(older);
+        assert_eq!(
+            (
+                TxGraphChangeSet::from_sqlite(&db_tx)?,
+                keychain_txout::ChangeSet::from_sqlite(&db_tx)?
+            ),
+            expected
+        );
+        assert_eq!(expected, newer);
+        Ok(())
+    }
+
+    #[test]
+    fn up_to_date_schemas_need_no_write_lock() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("locked.sqlite");
+        let mut writer = rusqlite::Connection::open(&path)?;
+        {
+            let db_tx = writer.transaction()?;
+            ChangeSet::init_sqlite_tables(&db_tx)?;
+            db_tx.commit()?;
         }
 
-        let mut statement = db_tx.prepare_cached(&format!(
-            "REPLACE INTO {}(txid, block_height, block_hash, confirmation_time) VALUES(:txid, :block_height, :block_hash, :confirmation_time)",
-            Self::ANCHORS_TABLE_NAME,
-        ))?;
-        let mut statement_txid = db_tx.prepare_cached(&format!(
-            "INSERT OR IGNORE INTO {}(txid) VALUES(:txid)",
-            Self::TXS_TABLE_NAME,
-        ))?;
-        for (anchor, txid) in &self.anchors {
-            let anchor_block = anchor.anchor_block();
-            statement_txid.execute(named_params! {
-                ":txid": Impl(*txid)
-            })?;
-            statement.execute(named_params! {
-                ":txid": Impl(*txid),
-                ":block_height": anchor_block.height,
-                ":block_hash": Impl(anchor_block.hash),
-                ":confirmation_time": anchor.confirmation_time,
-            })?;
+        // another connection holds the write lock
+        let _write_lock =
+            writer.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        let mut reader = rusqlite::Connection::open(&path)?;
+        reader.busy_timeout(core::time::Duration::ZERO)?;
+        let db_tx = reader.transaction()?;
+        ChangeSet::init_sqlite_tables(&db_tx)?;
+        assert_eq!(ChangeSet::from_sqlite(&db_tx)?, ChangeSet::default());
+        db_tx.commit()?;
+        Ok(())
+    }
+
+    #[test]
+    fn migrating_read_only_connection_only_fails_when_pending() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("wallet.sqlite");
+        let open_read_only = || {
+            rusqlite::Connection::open_with_flags(&path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        };
+        {
+            let mut conn = rusqlite::Connection::open(&path)?;
+            let db_tx = conn.transaction()?;
+            migrate_schema(&db_tx, ChangeSet::SCHEMA_NAME, &[&ChangeSet::schema_v0()])?;
+            db_tx.commit()?;
+        }
+
+        // v1 is pending
+        let mut conn = open_read_only()?;
+        let db_tx = conn.transaction()?;
+        let err = ChangeSet::init_sqlite_tables(&db_tx).expect_err("must not migrate");
+        assert!(
+            err.to_string().contains("bdk_txgraph v1"),
+            "unexpected error: {}",
+            err
+        );
+        drop(db_tx);
+
+        {
+            let mut conn = rusqlite::Connection::open(&path)?;
+            let db_tx = conn.transaction()?;
+            ChangeSet::init_sqlite_tables(&db_tx)?;
+            db_tx.commit()?;
         }
 
+        // nothing pending
+        let db_tx = conn.transaction()?;
+        ChangeSet::init_sqlite_tables(&db_tx)?;
+        assert_eq!(ChangeSet::from_sqlite(&db_tx)?, ChangeSet::default());
         Ok(())
     }
-}
 
-impl local_chain::ChangeSet {
-    /// Schema name for the changeset.
-    pub const SCHEMA_NAME: &'static str = "bdk_localchain";
-    /// Name of sqlite table that stores blocks of [`LocalChain`](local_chain::LocalChain).
-    pub const BLOCKS_TABLE_NAME: &'static str = "bdk_blocks";
+    #[test]
+    fn block_blob_is_size_checked() -> anyhow::Result<()> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let block = bitcoin::constants::genesis_block(bitcoin::Network::Bitcoin);
 
-    /// Get v0 of sqlite [local_chain::ChangeSet] schema
-    pub fn schema_v0() -> String {
-        // blocks
-        format!(
-            "CREATE TABLE {} ( \
-            block_height INTEGER PRIMARY KEY NOT NULL, \
-            block_hash TEXT NOT NULL \
-            ) STRICT",
-            Self::BLOCKS_TABLE_NAME,
-        )
+        let Impl(loaded) = conn.query_row("SELECT ?1", [Impl(block.clone())], |row| {
+            row.get::<_, Impl<bitcoin::Block>>(0)
+        })?;
+        assert_eq!(loaded, block);
+
+        let oversized = vec![0_u8; MAX_BLOCK_BLOB_SIZE + 1];
+        let res = conn.query_row("SELECT ?1", [oversized], |row| {
+            row.get::<_, Impl<bitcoin::Block>>(0)
+        });
+        assert!(matches!(
+            res,
+            Err(rusqlite::Error::FromSqlConversionFailure(..))
+        ));
+        Ok(())
     }
 
-    /// Initialize sqlite tables for persisting [`local_chain::LocalChain`].
-    pub fn init_sqlite_tables(db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
-        migrate_schema(db_tx, Self::SCHEMA_NAME, &[&Self::schema_v0()])
+    #[test]
+    fn corrupt_stored_tx_names_its_txid() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let tx = bitcoin::Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![bitcoin::TxOut {
+                value: bitcoin::Amount::from_sat(21_000),
+                script_pubkey: bitcoin::ScriptBuf::new(),
+            }],
+        };
+        let txid = tx.compute_txid();
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        ChangeSet::init_sqlite_tables(&db_tx)?;
+        ChangeSet {
+            txs: [Arc::new(tx)].into(),
+            ..Default::default()
+        }
+        .persist_to_sqlite(&db_tx)?;
+        db_tx.execute(
+            &format!(
+                "UPDATE {} SET raw_tx=substr(raw_tx, 1, 5)",
+                ChangeSet::TXS_TABLE_NAME
+            ),
+            (),
+        )?;
+
+        let source = match ChangeSet::from_sqlite(&db_tx).expect_err("must fail to decode") {
+            rusqlite::Error::FromSqlConversionFailure(_, _, source) => source,
+            err => panic!("unexpected error: {}", err),
+        };
+        let corrupt = source
+            .downcast_ref::<CorruptTxError>()
+            .expect("must be a corrupt tx error");
+        assert_eq!(corrupt.txid(), txid);
+        assert_eq!(corrupt.blob_size(), 5);
+        assert!(corrupt.to_string().contains(&txid.to_string()));
+        Ok(())
     }
 
-    /// Construct a [`LocalChain`](local_chain::LocalChain) from sqlite database.
-    ///
-    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
-    pub fn from_sqlite(db_tx: &rusqlite::Transaction) -> rusqlite::Result<Self> {
-        let mut changeset = Self::default();
+    #[test]
+    fn decode_errors_name_the_target_type() -> anyhow::Result<()> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let err = conn
+            .query_row("SELECT 'zz'", [], |row| {
+                row.get::<_, Impl<bitcoin::BlockHash>>(0)
+                    .map(Impl::into_inner)
+            })
+            .expect_err("must fail to parse block hash");
+        let source = match err {
+            rusqlite::Error::FromSqlConversionFailure(_, _, source) => source,
+            err => panic!("unexpected error: {}", err),
+        };
+        let decode_error = source
+            .downcast_ref::<DecodeError>()
+            .expect("must be a decode error");
+        assert!(decode_error.type_name().ends_with("BlockHash"));
+        assert!(decode_error.to_string().starts_with("failed to decode"));
+        Ok(())
+    }
 
-        let mut statement = db_tx.prepare(&format!(
-            "SELECT block_height, block_hash FROM {}",
-            Self::BLOCKS_TABLE_NAME,
-        ))?;
-        let row_iter = statement.query_map([], |row| {
-            Ok((
-                row.get::<_, u32>("block_height")?,
-                row.get::<_, Impl<bitcoin::BlockHash>>("block_hash")?,
-            ))
+    #[test]
+    fn get_sql_reports_failing_column() -> anyhow::Result<()> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let err = conn
+            .query_row("SELECT 'not a txid' AS spending_txid", [], |row| {
+                get_sql::<Impl<bitcoin::Txid>>(row, "spending_txid").map(Impl::into_inner)
+            })
+            .expect_err("must fail to parse txid");
+        assert!(matches!(
+            err,
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, _)
+        ));
+        assert!(err.to_string().contains("column `spending_txid`"));
+        Ok(())
+    }
+
+    #[test]
+    fn script_hashes_are_length_checked() -> anyhow::Result<()> {
+        use bitcoin::{ScriptHash, WScriptHash};
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let script = bitcoin::ScriptBuf::from_bytes(vec![0x51]);
+
+        let (script_hash, wscript_hash) = (script.script_hash(), script.wscript_hash());
+        let (Impl(loaded_script_hash), Impl(loaded_wscript_hash)) = conn.query_row(
+            "SELECT ?1, ?2",
+            (Impl(script_hash), Impl(wscript_hash)),
+            |row| {
+                Ok((
+                    row.get::<_, Impl<ScriptHash>>(0)?,
+                    row.get::<_, Impl<WScriptHash>>(1)?,
+                ))
+            },
+        )?;
+        assert_eq!(loaded_script_hash, script_hash);
+        assert_eq!(loaded_wscript_hash, wscript_hash);
+
+        // each is rejected at the length of the other
+        let res = conn.query_row("SELECT ?1", [Impl(wscript_hash)], |row| {
+            row.get::<_, Impl<ScriptHash>>(0)
+        });
+        assert!(matches!(
+            res,
+            Err(rusqlite::Error::FromSqlConversionFailure(..))
+        ));
+        let res = conn.query_row("SELECT ?1", [Impl(script_hash)], |row| {
+            row.get::<_, Impl<WScriptHash>>(0)
+        });
+        assert!(matches!(
+            res,
+            Err(rusqlite::Error::FromSqlConversionFailure(..))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn taproot_spend_data_can_be_stored() -> anyhow::Result<()> {
+        use bitcoin::hashes::Hash;
+        use bitcoin::taproot::{ControlBlock, TapNodeHash};
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        let node_hash = TapNodeHash::from_byte_array([7; 32]);
+        let Impl(loaded) = conn.query_row("SELECT ?1", [Impl(node_hash)], |row| {
+            row.get::<_, Impl<TapNodeHash>>(0)
         })?;
-        for row in row_iter {
-            let (height, Impl(hash)) = row?;
-            changeset.blocks.insert(height, Some(hash));
-        }
+        assert_eq!(loaded, node_hash);
+        let res = conn.query_row("SELECT ?1", [vec![0_u8; 31]], |row| {
+            row.get::<_, Impl<TapNodeHash>>(0)
+        });
+        assert!(matches!(
+            res,
+            Err(rusqlite::Error::FromSqlConversionFailure(..))
+        ));
+
+        // leaf version 0xc0, followed by an x-only internal key and one merkle branch node
+        let mut control_block_bytes = vec![0xc0];
+        control_block_bytes.extend_from_slice(&[
+            0x79, 0xbe, 0x66, 0x7e, 0xf9, 0xdc, 0xbb, 0xac, 0x55, 0xa0, 0x62, 0x95, 0xce, 0x87,
+            0x0b, 0x07, 0x02, 0x9b, 0xfc, 0xdb, 0x2d, 0xce, 0x28, 0xd9, 0x59, 0xf2, 0x81, 0x5b,
+            0x16, 0xf8, 0x17, 0x98,
+        ]);
+        control_block_bytes.extend_from_slice(&[7; 32]);
+        let control_block = ControlBlock::decode(&control_block_bytes)?;
+        let Impl(loaded) = conn.query_row("SELECT ?1", [Impl(control_block.clone())], |row| {
+            row.get::<_, Impl<ControlBlock>>(0)
+        })?;
+        assert_eq!(loaded, control_block);
+        Ok(())
+    }
 
-        Ok(changeset)
+    #[test]
+    fn descriptor_hex_keys_are_stored_lowercase() -> anyhow::Result<()> {
+        use miniscript::{Descriptor, DescriptorPublicKey};
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let key = "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+        let lower = Descriptor::<DescriptorPublicKey>::from_str(&format!("wpkh({})", key))?;
+        let upper =
+            Descriptor::<DescriptorPublicKey>::from_str(&format!("wpkh({})", key.to_uppercase()))?;
+
+        let (stored_lower, stored_upper): (String, String) =
+            conn.query_row("SELECT ?1, ?2", [Impl(lower), Impl(upper)], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+        assert_eq!(stored_lower, stored_upper);
+        assert!(stored_lower.contains(key));
+        Ok(())
     }
 
-    /// Persist `changeset` to the sqlite database.
-    ///
-    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
-    pub fn persist_to_sqlite(&self, db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
-        let mut replace_statement = db_tx.prepare_cached(&format!(
-            "REPLACE INTO {}(block_height, block_hash) VALUES(:block_height, :block_hash)",
-            Self::BLOCKS_TABLE_NAME,
-        ))?;
-        let mut delete_statement = db_tx.prepare_cached(&format!(
-            "DELETE FROM {} WHERE block_height=:block_height",
-            Self::BLOCKS_TABLE_NAME,
-        ))?;
-        for (&height, &hash) in &self.blocks {
-            match hash {
-                Some(hash) => replace_statement.execute(named_params! {
-                    ":block_height": height,
-                    ":block_hash": Impl(hash),
-                })?,
-                None => delete_statement.execute(named_params! {
-                    ":block_height": height,
-                })?,
-            };
+    #[test]
+    fn backfill_descriptor_ids_populates_added_column() -> anyhow::Result<()> {
+        use miniscript::{Descriptor, DescriptorPublicKey};
+        let descriptors = [
+            "wpkh(02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9)",
+            "tr(02e493dbf1c10d80f3581e4904930b1404cc6c13900ee0758474fa94abe8c4cd13)",
+        ]
+        .iter()
+        .map(|s| Descriptor::<DescriptorPublicKey>::from_str(s))
+        .collect::<Result<Vec<_>, _>>()?;
+
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        db_tx.execute_batch("CREATE TABLE labels ( descriptor TEXT NOT NULL, label TEXT )")?;
+        for (i, descriptor) in descriptors.iter().enumerate() {
+            // two rows per descriptor
+            for label in [format!("{}a", i), format!("{}b", i)] {
+                db_tx.execute(
+                    "INSERT INTO labels(descriptor, label) VALUES(?1, ?2)",
+                    rusqlite::params![Impl(descriptor.clone()), label],
+                )?;
+            }
+        }
+        db_tx.execute_batch("ALTER TABLE labels ADD COLUMN descriptor_id BLOB")?;
+
+        let updated = backfill_descriptor_ids(&db_tx, "labels", "descriptor", "descriptor_id")?;
+        assert_eq!(updated, 4);
+
+        let mut statement = db_tx.prepare("SELECT descriptor, descriptor_id FROM labels")?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, Impl<Descriptor<DescriptorPublicKey>>>(0)?,
+                    row.get::<_, Impl<DescriptorId>>(1)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(rows.len(), 4);
+        for (Impl(descriptor), Impl(descriptor_id)) in rows {
+            assert_eq!(descriptor_id, descriptor.descriptor_id());
         }
+        Ok(())
+    }
 
+    #[test]
+    fn compressed_public_key_rejects_uncompressed_encoding() -> anyhow::Result<()> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let secret_key = bitcoin::secp256k1::SecretKey::from_slice(&[1; 32])?;
+        let public_key = bitcoin::secp256k1::PublicKey::from_secret_key(&secp, &secret_key);
+        let compressed = bitcoin::CompressedPublicKey(public_key);
+
+        let Impl(loaded) = conn.query_row("SELECT ?1", [Impl(compressed)], |row| {
+            row.get::<_, Impl<bitcoin::CompressedPublicKey>>(0)
+        })?;
+        assert_eq!(loaded, compressed);
+
+        for invalid in [public_key.serialize_uncompressed().to_vec(), vec![5; 33]] {
+            let res = conn.query_row("SELECT ?1", [invalid], |row| {
+                row.get::<_, Impl<bitcoin::CompressedPublicKey>>(0)
+            });
+            assert!(matches!(
+                res,
+                Err(rusqlite::Error::FromSqlConversionFailure(..))
+            ));
+        }
         Ok(())
     }
-}
 
-#[cfg(feature = "miniscript")]
-impl keychain_txout::ChangeSet {
-    /// Schema name for the changeset.
-    pub const SCHEMA_NAME: &'static str = "bdk_keychaintxout";
-    /// Name for table that stores last revealed indices per descriptor id.
-    pub const LAST_REVEALED_TABLE_NAME: &'static str = "bdk_descriptor_last_revealed";
+    #[test]
+    fn derivation_path_is_stored_as_text() -> anyhow::Result<()> {
+        use bitcoin::bip32::DerivationPath;
+        let conn = rusqlite::Connection::open_in_memory()?;
+        for text in ["m/84'/0'/0'/1/5", "m"] {
+            let path = DerivationPath::from_str(text)?;
+            let stored: String =
+                conn.query_row("SELECT ?1", [Impl(path.clone())], |row| row.get(0))?;
+            assert_eq!(stored, text);
+            let Impl(loaded) = conn.query_row("SELECT ?1", [stored], |row| {
+                row.get::<_, Impl<DerivationPath>>(0)
+            })?;
+            assert_eq!(loaded, path);
+        }
 
-    /// Get v0 of sqlite [keychain_txout::ChangeSet] schema
-    pub fn schema_v0() -> String {
-        format!(
-            "CREATE TABLE {} ( \
-            descriptor_id TEXT PRIMARY KEY NOT NULL, \
-            last_revealed INTEGER NOT NULL \
-            ) STRICT",
-            Self::LAST_REVEALED_TABLE_NAME,
-        )
+        for invalid in ["m/84'/x", "m//1", "m/2147483648"] {
+            let res = conn.query_row("SELECT ?1", [invalid], |row| {
+                row.get::<_, Impl<DerivationPath>>(0)
+            });
+            assert!(
+                matches!(res, Err(rusqlite::Error::FromSqlConversionFailure(..))),
+                "{} must not load",
+                invalid
+            );
+        }
+        Ok(())
     }
 
-    /// Initialize sqlite tables for persisting
-    /// [`KeychainTxOutIndex`](keychain_txout::KeychainTxOutIndex).
-    pub fn init_sqlite_tables(db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
-        migrate_schema(db_tx, Self::SCHEMA_NAME, &[&Self::schema_v0()])
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid_is_stored_as_16_byte_blob() -> anyhow::Result<()> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let uuid = uuid::Uuid::from_bytes([7; 16]);
+        let stored: Vec<u8> = conn.query_row("SELECT ?1", [Impl(uuid)], |row| row.get(0))?;
+        assert_eq!(stored, [7; 16]);
+        let Impl(loaded) = conn.query_row("SELECT ?1", [stored], |row| {
+            row.get::<_, Impl<uuid::Uuid>>(0)
+        })?;
+        assert_eq!(loaded, uuid);
+
+        let res = conn.query_row("SELECT ?1", [vec![7_u8; 15]], |row| {
+            row.get::<_, Impl<uuid::Uuid>>(0)
+        });
+        assert!(matches!(
+            res,
+            Err(rusqlite::Error::FromSqlConversionFailure(
+                0,
+                rusqlite::types::Type::Blob,
+                ref err,
+            )) if matches!(
+                err.downcast_ref::<FromSqlError>(),
+                Some(FromSqlError::InvalidBlobSize {
+                    expected_size: 16,
+                    blob_size: 15,
+                })
+            )
+        ));
+        Ok(())
     }
 
-    /// Construct [`KeychainTxOutIndex`](keychain_txout::KeychainTxOutIndex) from sqlite database
-    /// and given parameters.
-    ///
-    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
-    pub fn from_sqlite(db_tx: &rusqlite::Transaction) -> rusqlite::Result<Self> {
-        let mut changeset = Self::default();
+    #[test]
+    fn btc_amount_is_stored_as_fixed_point_text() -> anyhow::Result<()> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        for (sat, text) in [
+            (0, "0.00000000"),
+            (50_000, "0.00050000"),
+            (2_100_000_000_000_000, "21000000.00000000"),
+        ] {
+            let amount = bitcoin::Amount::from_sat(sat);
+            let stored: String = conn.query_row("SELECT ?1", [SqlBtc(amount)], |row| row.get(0))?;
+            assert_eq!(stored, text);
+            let SqlBtc(loaded) =
+                conn.query_row("SELECT ?1", [stored], |row| row.get::<_, SqlBtc>(0))?;
+            assert_eq!(loaded, amount);
+        }
 
-        let mut statement = db_tx.prepare(&format!(
-            "SELECT descriptor_id, last_revealed FROM {}",
-            Self::LAST_REVEALED_TABLE_NAME,
-        ))?;
-        let row_iter = statement.query_map([], |row| {
-            Ok((
-                row.get::<_, Impl<DescriptorId>>("descriptor_id")?,
-                row.get::<_, u32>("last_revealed")?,
-            ))
-        })?;
-        for row in row_iter {
-            let (Impl(descriptor_id), last_revealed) = row?;
-            changeset.last_revealed.insert(descriptor_id, last_revealed);
+        for invalid in ["0.000000001", "1.000000000", "-1.0", "one"] {
+            let res = conn.query_row("SELECT ?1", [invalid], |row| row.get::<_, SqlBtc>(0));
+            assert!(res.is_err(), "{} must not load", invalid);
         }
+        Ok(())
+    }
 
-        Ok(changeset)
+    #[test]
+    fn consensus_encodable_types_can_be_stored() -> anyhow::Result<()> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let txout = TxOut {
+            value: bitcoin::Amount::ONE_BTC,
+            script_pubkey: bitcoin::ScriptBuf::from_bytes(vec![0x51]),
+        };
+        let txin = TxIn {
+            previous_output: bitcoin::OutPoint::new(hash!("prev"), 1),
+            ..Default::default()
+        };
+
+        let (SqlConsensus(loaded_txout), SqlConsensus(loaded_txin)) = conn.query_row(
+            "SELECT ?1, ?2",
+            rusqlite::params![SqlConsensus(txout.clone()), SqlConsensus(txin.clone())],
+            |row| {
+                Ok((
+                    row.get::<_, SqlConsensus<TxOut>>(0)?,
+                    row.get::<_, SqlConsensus<TxIn>>(1)?,
+                ))
+            },
+        )?;
+        assert_eq!(loaded_txout, txout);
+        assert_eq!(loaded_txin, txin);
+
+        let mut trailing = bitcoin::consensus::serialize(&txout);
+        trailing.push(0);
+        let res = conn.query_row("SELECT ?1", [trailing], |row| {
+            row.get::<_, SqlConsensus<TxOut>>(0)
+                .map(SqlConsensus::into_inner)
+        });
+        assert!(matches!(
+            res,
+            Err(rusqlite::Error::FromSqlConversionFailure(..))
+        ));
+        Ok(())
     }
 
-    /// Persist `changeset` to the sqlite database.
-    ///
-    /// Remember to call [`Self::init_sqlite_tables`] beforehand.
-    pub fn persist_to_sqlite(&self, db_tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
-        let mut statement = db_tx.prepare_cached(&format!(
-            "REPLACE INTO {}(descriptor_id, last_revealed) VALUES(:descriptor_id, :last_revealed)",
-            Self::LAST_REVEALED_TABLE_NAME,
-        ))?;
-        for (&descriptor_id, &last_revealed) in &self.last_revealed {
-            statement.execute(named_params! {
-                ":descriptor_id": Impl(descriptor_id),
-                ":last_revealed": last_revealed,
-            })?;
+    #[test]
+    fn bounded_integers_are_checked_on_load() -> anyhow::Result<()> {
+        use core::num::NonZeroU32;
+        type GapLimit = SqlBounded<u32, 1, 1000>;
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let Impl(loaded) =
+            conn.query_row("SELECT 20", [], |row| row.get::<_, Impl<NonZeroU32>>(0))?;
+        assert_eq!(loaded.get(), 20);
+        assert!(conn
+            .query_row("SELECT 0", [], |row| row.get::<_, Impl<NonZeroU32>>(0))
+            .is_err());
+
+        for value in [1, 20, 1000] {
+            let SqlBounded(loaded) =
+                conn.query_row("SELECT ?1", [GapLimit::from(value)], |row| {
+                    row.get::<_, GapLimit>(0)
+                })?;
+            assert_eq!(loaded, value);
         }
-
+        for value in [0_i64, 1001, -1] {
+            assert!(conn
+                .query_row("SELECT ?1", [value], |row| row.get::<_, GapLimit>(0))
+                .is_err());
+        }
+        assert!(conn
+            .query_row("SELECT ?1", [GapLimit::from(1001_u32)], |_| Ok(()))
+            .is_err());
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    #[test]
+    fn bounded_text_is_checked_on_write_and_load() -> anyhow::Result<()> {
+        type Memo = SqlBoundedText<8>;
+        let conn = rusqlite::Connection::open_in_memory()?;
+        for text in ["", "rent", "12345678"] {
+            let SqlBoundedText(loaded) =
+                conn.query_row("SELECT ?1", [Memo::from(text.to_string())], |row| {
+                    row.get::<_, Memo>(0)
+                })?;
+            assert_eq!(loaded, text);
+        }
+        // the limit is in bytes, not characters
+        let too_long = Memo::from("ééééé".to_string());
+        let err = conn
+            .query_row("SELECT ?1", [too_long], |_| Ok(()))
+            .expect_err("must refuse to write");
+        assert!(matches!(
+            err,
+            rusqlite::Error::ToSqlConversionFailure(err)
+                if err.downcast_ref() == Some(&TextTooLongError { max_len: 8, len: 10 })
+        ));
+        assert!(conn
+            .query_row("SELECT '123456789'", [], |row| row.get::<_, Memo>(0))
+            .is_err());
+        Ok(())
+    }
 
-    use bdk_testenv::{anyhow, hash};
-    use bitcoin::{absolute, transaction, TxIn, TxOut};
+    #[test]
+    fn tx_version_can_be_queried_as_integer() -> anyhow::Result<()> {
+        use bitcoin::transaction::Version;
+        let conn = rusqlite::Connection::open_in_memory()?;
+        for version in [Version::ONE, Version::TWO, Version(3), Version(-1)] {
+            let (Impl(loaded), raw) = conn.query_row("SELECT ?1, ?1", [Impl(version)], |row| {
+                Ok((row.get::<_, Impl<Version>>(0)?, row.get::<_, i64>(1)?))
+            })?;
+            assert_eq!(loaded, version);
+            assert_eq!(raw, i64::from(version.0));
+        }
+        assert!(conn
+            .query_row("SELECT ?1", [i64::from(i32::MAX) + 1], |row| {
+                row.get::<_, Impl<Version>>(0)
+            })
+            .is_err());
+        Ok(())
+    }
 
     #[test]
-    fn can_persist_anchors_and_txs_independently() -> anyhow::Result<()> {
-        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+    fn rbf_signal_is_stored_as_integer_boolean() -> anyhow::Result<()> {
+        use bitcoin::Sequence;
         let mut conn = rusqlite::Connection::open_in_memory()?;
-
-        // init tables
-        {
-            let db_tx = conn.transaction()?;
-            ChangeSet::init_sqlite_tables(&db_tx)?;
-            db_tx.commit()?;
+        let db_tx = conn.transaction()?;
+        db_tx.execute_batch(
+            "CREATE TABLE test_rbf ( vin INTEGER PRIMARY KEY, rbf_signaled INTEGER NOT NULL ) STRICT",
+        )?;
+        let sequences = [
+            Sequence::ZERO,
+            Sequence::ENABLE_RBF_NO_LOCKTIME,
+            Sequence::ENABLE_LOCKTIME_NO_RBF,
+            Sequence::MAX,
+        ];
+        for (vin, &sequence) in sequences.iter().enumerate() {
+            db_tx.execute(
+                "INSERT INTO test_rbf(vin, rbf_signaled) VALUES(?1, ?2)",
+                rusqlite::params![vin, SqlRbfSignaled::from(sequence)],
+            )?;
         }
 
+        let loaded = db_tx
+            .prepare("SELECT rbf_signaled FROM test_rbf ORDER BY vin")?
+            .query_map([], |row| row.get::<_, SqlRbfSignaled>("rbf_signaled"))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let expected = sequences
+            .iter()
+            .map(|sequence| SqlRbfSignaled(sequence.is_rbf()))
+            .collect::<Vec<_>>();
+        assert_eq!(loaded, expected);
+        let replaceable: i64 = db_tx.query_row(
+            "SELECT COUNT(*) FROM test_rbf WHERE rbf_signaled",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(replaceable, 2);
+
         let tx = bitcoin::Transaction {
             version: transaction::Version::TWO,
             lock_time: absolute::LockTime::ZERO,
-            input: vec![TxIn::default()],
-            output: vec![TxOut::NULL],
-        };
-        let tx = Arc::new(tx);
-        let txid = tx.compute_txid();
-        let anchor = ConfirmationBlockTime {
-            block_id: BlockId {
-                height: 21,
-                hash: hash!("anchor"),
-            },
-            confirmation_time: 1342,
+            input: vec![TxIn {
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            }],
+            output: vec![],
         };
+        assert_eq!(SqlRbfSignaled::from(&tx), SqlRbfSignaled(true));
 
-        // First persist the anchor
-        {
-            let changeset = ChangeSet {
-                anchors: [(anchor, txid)].into(),
-                ..Default::default()
-            };
-            let db_tx = conn.transaction()?;
-            changeset.persist_to_sqlite(&db_tx)?;
-            db_tx.commit()?;
-        }
+        let res = db_tx.query_row("SELECT 2", [], |row| row.get::<_, SqlRbfSignaled>(0));
+        assert!(matches!(
+            res,
+            Err(rusqlite::Error::IntegralValueOutOfRange(0, 2))
+        ));
+        Ok(())
+    }
 
-        // Now persist the tx
-        {
-            let changeset = ChangeSet {
-                txs: [tx.clone()].into(),
-                ..Default::default()
-            };
-            let db_tx = conn.transaction()?;
-            changeset.persist_to_sqlite(&db_tx)?;
-            db_tx.commit()?;
-        }
+    #[test]
+    fn fee_rate_can_be_stored_as_fractional_sat_per_vb() -> anyhow::Result<()> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let fee_rate = bitcoin::FeeRate::from_sat_per_kwu(375);
 
-        // Loading changeset from sqlite should succeed
-        {
-            let db_tx = conn.transaction()?;
-            let changeset = ChangeSet::from_sqlite(&db_tx)?;
-            db_tx.commit()?;
-            assert!(changeset.txs.contains(&tx));
-            assert!(changeset.anchors.contains(&(anchor, txid)));
-        }
+        let stored: f64 = conn.query_row("SELECT ?1", [SqlSatPerVb(fee_rate)], |row| row.get(0))?;
+        assert_eq!(stored, 1.5);
 
+        let SqlSatPerVb(loaded) =
+            conn.query_row("SELECT ?1", [stored], |row| row.get::<_, SqlSatPerVb>(0))?;
+        assert_eq!(loaded, fee_rate);
+
+        for invalid in [-1.0, f64::INFINITY] {
+            let res = conn.query_row("SELECT ?1", [invalid], |row| row.get::<_, SqlSatPerVb>(0));
+            assert!(res.is_err(), "{} sat/vB must not load", invalid);
+        }
         Ok(())
     }
 
@@ -0,0 +1,1237 @@
+//! Configuration and maintenance of the [`rusqlite::Connection`] used for persistence.
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+use rusqlite::Connection;
+
+/// Options applied to a [`Connection`] before its tables are initialized.
+///
+/// The default options leave the connection as opened.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionOptions {
+    /// Trade durability for write throughput by keeping the rollback journal in memory and never
+    /// syncing to disk (`journal_mode=MEMORY` and `synchronous=OFF`).
+    ///
+    /// **This is unsafe against crashes.** A crash or power loss in the middle of a write can
+    /// corrupt the database. Only use this for databases that can be rebuilt from scratch, such as
+    /// test fixtures and disposable caches.
+    pub ephemeral: bool,
+    /// Use a write-ahead log (`journal_mode=WAL`) with `synchronous=NORMAL`.
+    ///
+    /// In WAL mode, `synchronous=NORMAL` avoids an fsync on every commit and is still safe
+    /// against application crashes. A power loss or OS crash may roll back the most recently
+    /// committed transactions, but cannot corrupt the database. Without this option, SQLite keeps
+    /// its default rollback journal with `synchronous=FULL`. Ignored if
+    /// [`ephemeral`](Self::ephemeral) is set.
+    pub wal: bool,
+    /// Number of pages the write-ahead log may grow to before a commit checkpoints it
+    /// automatically (`wal_autocheckpoint`). Zero disables automatic checkpoints.
+    ///
+    /// SQLite defaults to 1000 pages. Automatic checkpoints are passive, so a busy reader can
+    /// still keep the log from shrinking; see [`truncate_wal_above`] for a hard cap.
+    pub wal_autocheckpoint_pages: Option<u32>,
+    /// Page size of the database file in bytes (`page_size`).
+    ///
+    /// Must be a power of two between 512 and 65536. This only takes effect for a database that
+    /// has no tables yet (or for the next `VACUUM`).
+    pub page_size: Option<u32>,
+    /// Suggested maximum number of database pages held in memory (`cache_size`).
+    ///
+    /// A negative value is interpreted by SQLite as a limit in KiB instead of pages.
+    pub cache_size: Option<i64>,
+    /// Maximum number of bytes of the database file to access through memory-mapped I/O
+    /// (`mmap_size`). Zero disables memory-mapped I/O.
+    pub mmap_size: Option<u64>,
+    /// Overwrite deleted content with zeros (`secure_delete=ON`).
+    ///
+    /// By default, SQLite leaves the bytes of deleted rows in freed pages of the database file,
+    /// where forensic tools can recover them. This matters for wallets that "forget" transactions
+    /// or labels. The cost is write amplification: every delete rewrites the freed space, and
+    /// freed pages are written out instead of merely being added to the free list. In WAL mode the
+    /// old content also remains in the log until it is checkpointed.
+    pub secure_delete: bool,
+    /// Keep track of freed pages so that they can be returned to the filesystem a few at a time
+    /// with [`incremental_vacuum`] (`auto_vacuum=INCREMENTAL`).
+    ///
+    /// Deleted rows (e.g. anchors removed by reorgs) otherwise leave free pages in the file that
+    /// only a full `VACUUM`, which rewrites the whole file, gives back. SQLite can only switch to
+    /// incremental auto-vacuum before the first table is created, so [`apply`](Self::apply)
+    /// returns an error if the database already has tables and is not in this mode.
+    pub incremental_auto_vacuum: bool,
+}
+
+impl ConnectionOptions {
+    /// Apply the options to `conn`.
+    ///
+    /// Call this right after opening the connection and before initializing any tables.
+    pub fn apply(&self, conn: &Connection) -> rusqlite::Result<()> {
+        // the page size has to be set before anything (including the journal mode) is written
+        if let Some(page_size) = self.page_size {
+            if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+                return Err(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+                    Some(format!(
+                        "invalid page size {}, must be a power of two between 512 and 65536",
+                        page_size
+                    )),
+                ));
+            }
+            conn.pragma_update(None, "page_size", page_size)?;
+        }
+        if self.incremental_auto_vacuum {
+            // 2 is INCREMENTAL
+            let auto_vacuum =
+                conn.pragma_query_value(None, "auto_vacuum", |row| row.get::<_, u32>(0))?;
+            if auto_vacuum != 2 {
+                let has_tables = conn.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type='table')",
+                    [],
+                    |row| row.get::<_, bool>(0),
+                )?;
+                if has_tables {
+                    return Err(rusqlite::Error::SqliteFailure(
+                        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+                        Some(
+                            "incremental auto-vacuum must be enabled before any table is created"
+                                .into(),
+                        ),
+                    ));
+                }
+                conn.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
+            }
+        }
+        if self.ephemeral {
+            conn.pragma_update_and_check(None, "journal_mode", "MEMORY", |_| Ok(()))?;
+            conn.pragma_update(None, "synchronous", "OFF")?;
+        } else if self.wal {
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+            conn.pragma_update(None, "synchronous", "NORMAL")?;
+        }
+        if let Some(pages) = self.wal_autocheckpoint_pages {
+            conn.pragma_update_and_check(None, "wal_autocheckpoint", pages, |_| Ok(()))?;
+        }
+        if let Some(cache_size) = self.cache_size {
+            conn.pragma_update(None, "cache_size", cache_size)?;
+        }
+        if let Some(mmap_size) = self.mmap_size {
+            let mmap_size = i64::try_from(mmap_size).unwrap_or(i64::MAX);
+            conn.pragma_update_and_check(None, "mmap_size", mmap_size, |_| Ok(()))?;
+        }
+        if self.secure_delete {
+            conn.pragma_update_and_check(None, "secure_delete", true, |_| Ok(()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Enable enforcement of foreign key constraints on `conn`.
+///
+/// SQLite does not enforce `REFERENCES` constraints unless `PRAGMA foreign_keys=ON` is issued on
+/// each connection. The pragma has no effect inside a transaction, so call this right after
+/// opening the connection and before running any migrations.
+///
+/// Existing data is checked with `PRAGMA foreign_key_check`. If violations are found, enforcement
+/// is turned back off and an error listing the offending tables is returned. This is opt-in since
+/// databases written without enforcement may contain such violations.
+pub fn enable_foreign_keys(conn: &Connection) -> rusqlite::Result<()> {
+    conn.pragma_update(None, "foreign_keys", true)?;
+    let violations = conn
+        .prepare("PRAGMA foreign_key_check")?
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if violations.is_empty() {
+        return Ok(());
+    }
+    conn.pragma_update(None, "foreign_keys", false)?;
+    let violations = violations
+        .iter()
+        .map(|(table, rowid)| match rowid {
+            Some(rowid) => format!("{}(rowid {})", table, rowid),
+            None => table.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    Err(rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY),
+        Some(format!(
+            "cannot enable foreign keys, existing rows violate constraints: {}",
+            violations
+        )),
+    ))
+}
+
+/// Run the read-only `f` within a transaction on `conn`.
+///
+/// The connection is put into `query_only` mode for the duration of `f`, so any attempt by `f`
+/// to modify the database fails with `SQLITE_READONLY`. The transaction is always rolled back and
+/// the previous `query_only` setting is restored afterwards, whether `f` succeeds, fails or
+/// panics.
+pub fn read_only<T>(
+    conn: &mut Connection,
+    f: impl FnOnce(&rusqlite::Transaction) -> rusqlite::Result<T>,
+) -> rusqlite::Result<T> {
+    let was_query_only =
+        conn.pragma_query_value(None, "query_only", |row| row.get::<_, bool>(0))?;
+    conn.pragma_update(None, "query_only", true)?;
+    let guard = QueryOnlyGuard {
+        conn,
+        was_query_only,
+    };
+    let res =
+        rusqlite::Transaction::new_unchecked(guard.conn, rusqlite::TransactionBehavior::Deferred)
+            .and_then(|db_tx| f(&db_tx));
+    guard.restore()?;
+    res
+}
+
+/// Restores the `query_only` setting of [`read_only`] when dropped, e.g. while unwinding.
+struct QueryOnlyGuard<'c> {
+    conn: &'c Connection,
+    was_query_only: bool,
+}
+
+impl QueryOnlyGuard<'_> {
+    fn restore(self) -> rusqlite::Result<()> {
+        let res = self
+            .conn
+            .pragma_update(None, "query_only", self.was_query_only);
+        core::mem::forget(self);
+        res
+    }
+}
+
+impl Drop for QueryOnlyGuard<'_> {
+    fn drop(&mut self) {
+        let _ = self
+            .conn
+            .pragma_update(None, "query_only", self.was_query_only);
+    }
+}
+
+/// Read every row of `table_names` so that their pages are held in SQLite's page cache.
+///
+/// On a cold start, the first load reads every page from disk on demand. Scanning the tables
+/// (e.g. the `TABLE_NAMES` of each changeset) beforehand, possibly while doing other work, lets
+/// the real load be served from memory. This only helps if the cache is large enough to hold the
+/// tables (see [`ConnectionOptions::cache_size`]), and indexes are not warmed.
+pub fn warm_cache(conn: &Connection, table_names: &[&str]) -> rusqlite::Result<()> {
+    super::check_identifiers(table_names)?;
+    for table_name in table_names {
+        let mut statement = conn.prepare(&format!("SELECT * FROM {}", table_name))?;
+        let column_count = statement.column_count();
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            // reading the values pulls in overflow pages of large values too
+            for i in 0..column_count {
+                row.get_ref(i)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// How [`wal_checkpoint`] should treat concurrent readers and writers.
+///
+/// See the [SQLite documentation](https://www.sqlite.org/pragma.html#pragma_wal_checkpoint)
+/// for details of each mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WalCheckpointMode {
+    /// Checkpoint as many frames as possible without waiting for readers or writers.
+    #[default]
+    Passive,
+    /// Wait for writers, then checkpoint every frame.
+    Full,
+    /// Like [`Full`](Self::Full), then wait for readers so the next writer restarts the log.
+    Restart,
+    /// Like [`Restart`](Self::Restart), then truncate the `-wal` file to zero bytes.
+    Truncate,
+}
+
+impl WalCheckpointMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Passive => "PASSIVE",
+            Self::Full => "FULL",
+            Self::Restart => "RESTART",
+            Self::Truncate => "TRUNCATE",
+        }
+    }
+}
+
+/// Checkpoint the write-ahead log of `conn`, returning the number of frames in the log and the
+/// number of frames that were checkpointed.
+///
+/// SQLite checkpoints automatically once the log grows past a threshold. Calling this after a
+/// large batched persist lets readers avoid traversing a large log until then. Returns an error
+/// if `conn` is not in WAL mode, or with `SQLITE_BUSY` if a blocking `mode` could not complete.
+pub fn wal_checkpoint(conn: &Connection, mode: WalCheckpointMode) -> rusqlite::Result<(u32, u32)> {
+    let (busy, log_frames, checkpointed_frames) = conn.query_row(
+        &format!("PRAGMA wal_checkpoint({})", mode.as_str()),
+        [],
+        |row| {
+            Ok((
+                row.get::<_, bool>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        },
+    )?;
+    if busy {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            Some(format!(
+                "wal checkpoint ({}) could not complete",
+                mode.as_str()
+            )),
+        ));
+    }
+    match (
+        u32::try_from(log_frames),
+        u32::try_from(checkpointed_frames),
+    ) {
+        (Ok(log_frames), Ok(checkpointed_frames)) => Ok((log_frames, checkpointed_frames)),
+        _ => Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+            Some("cannot checkpoint, connection is not in WAL mode".into()),
+        )),
+    }
+}
+
+/// Handle of a background thread started with [`spawn_auto_checkpoint`].
+///
+/// Dropping the handle stops the thread (waiting for a checkpoint in progress to finish).
+#[derive(Debug)]
+pub struct CheckpointHandle {
+    stop: Option<std::sync::mpsc::Sender<()>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+    errors: std::sync::Arc<std::sync::Mutex<Vec<rusqlite::Error>>>,
+}
+
+impl CheckpointHandle {
+    /// Maximum number of errors kept between calls to [`Self::take_errors`].
+    pub const MAX_ERRORS: usize = 16;
+
+    /// Take the errors of the checkpoints that failed since the last call.
+    ///
+    /// The thread keeps checkpointing after an error. Only the most recent
+    /// [`MAX_ERRORS`](Self::MAX_ERRORS) errors are kept, so a handle whose errors are never taken
+    /// doesn't grow without bound.
+    pub fn take_errors(&self) -> Vec<rusqlite::Error> {
+        core::mem::take(
+            &mut *self
+                .errors
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        )
+    }
+}
+
+impl Drop for CheckpointHandle {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Spawn a thread that runs a [passive](WalCheckpointMode::Passive) checkpoint of the database of
+/// `conn` every `interval`, until the returned handle is dropped.
+///
+/// This keeps the write-ahead log from growing unbounded in long-running processes whose writes
+/// come in bursts. The thread uses its own connection to the database file of `conn`, so `conn`
+/// must not be an in-memory database, and must be in WAL mode. Failed checkpoints are reported
+/// through [`CheckpointHandle::take_errors`].
+pub fn spawn_auto_checkpoint(
+    conn: &Connection,
+    interval: std::time::Duration,
+) -> rusqlite::Result<CheckpointHandle> {
+    let path = match conn.path() {
+        Some(path) if !path.is_empty() => path,
+        _ => {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+                Some("cannot checkpoint an in-memory database from another connection".into()),
+            ))
+        }
+    };
+    let journal_mode =
+        conn.pragma_query_value(None, "journal_mode", |row| row.get::<_, String>(0))?;
+    if !journal_mode.eq_ignore_ascii_case("wal") {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+            Some("cannot checkpoint, connection is not in WAL mode".into()),
+        ));
+    }
+    let checkpoint_conn = Connection::open(path)?;
+    let (stop, stopped) = std::sync::mpsc::channel::<()>();
+    let errors = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let thread = std::thread::spawn({
+        let errors = errors.clone();
+        move || {
+            while let Err(std::sync::mpsc::RecvTimeoutError::Timeout) =
+                stopped.recv_timeout(interval)
+            {
+                if let Err(err) = wal_checkpoint(&checkpoint_conn, WalCheckpointMode::Passive) {
+                    let mut errors = errors
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    if errors.len() == CheckpointHandle::MAX_ERRORS {
+                        errors.remove(0);
+                    }
+                    errors.push(err);
+                }
+            }
+        }
+    });
+    Ok(CheckpointHandle {
+        stop: Some(stop),
+        thread: Some(thread),
+        errors,
+    })
+}
+
+/// Storage used by a database, as reported by [`storage_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageStats {
+    /// Size of a database page in bytes.
+    pub page_size: u32,
+    /// Number of pages in the main database file.
+    pub page_count: u64,
+    /// Number of frames in the write-ahead log, or `None` if the database is not in WAL mode.
+    pub wal_frames: Option<u32>,
+}
+
+impl StorageStats {
+    /// Size of the main database file in bytes.
+    pub fn database_size(&self) -> u64 {
+        self.page_count * u64::from(self.page_size)
+    }
+
+    /// Size of the write-ahead log in bytes, or `None` if the database is not in WAL mode.
+    ///
+    /// This is computed from the WAL file format (a 32-byte header followed by frames of a 24-byte
+    /// header and one page each).
+    pub fn wal_size(&self) -> Option<u64> {
+        self.wal_frames
+            .map(|frames| 32 + u64::from(frames) * (24 + u64::from(self.page_size)))
+    }
+}
+
+/// Report the storage used by the database of `conn`.
+///
+/// Unlike inspecting file sizes on the filesystem, this also works for in-memory and VFS-backed
+/// databases. In WAL mode, the number of WAL frames is obtained with a passive checkpoint, which
+/// may checkpoint frames that no reader needs as a side effect (but never blocks or truncates).
+pub fn storage_stats(conn: &Connection) -> rusqlite::Result<StorageStats> {
+    let page_size = conn.pragma_query_value(None, "page_size", |row| row.get::<_, u32>(0))?;
+    let page_count = conn.pragma_query_value(None, "page_count", |row| row.get::<_, u64>(0))?;
+    let journal_mode =
+        conn.pragma_query_value(None, "journal_mode", |row| row.get::<_, String>(0))?;
+    let wal_frames = if journal_mode.eq_ignore_ascii_case("wal") {
+        Some(wal_checkpoint(conn, WalCheckpointMode::Passive)?.0)
+    } else {
+        None
+    };
+    Ok(StorageStats {
+        page_size,
+        page_count,
+        wal_frames,
+    })
+}
+
+/// Run a [truncating](WalCheckpointMode::Truncate) checkpoint if the write-ahead log of `conn`
+/// is larger than `max_wal_size` bytes, returning whether it was truncated.
+///
+/// Call this after persisting to cap the size of the log in long-running services that write
+/// frequently. The size is the one reported by [`StorageStats::wal_size`]. Returns an error if
+/// `conn` is not in WAL mode, or with `SQLITE_BUSY` if readers kept the checkpoint from
+/// completing.
+pub fn truncate_wal_above(conn: &Connection, max_wal_size: u64) -> rusqlite::Result<bool> {
+    match storage_stats(conn)?.wal_size() {
+        Some(wal_size) if wal_size > max_wal_size => {
+            wal_checkpoint(conn, WalCheckpointMode::Truncate)?;
+            Ok(true)
+        }
+        Some(_) => Ok(false),
+        None => Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+            Some("cannot truncate, connection is not in WAL mode".into()),
+        )),
+    }
+}
+
+/// Return up to `max_pages` free pages of the database of `conn` to the filesystem
+/// (`incremental_vacuum`), returning the number of pages freed.
+///
+/// Calling this periodically (e.g. after persisting) reclaims space gradually, without the full
+/// rewrite of `VACUUM`. It requires [`ConnectionOptions::incremental_auto_vacuum`], and frees
+/// nothing otherwise. Like other writes, it fails within a read-only transaction.
+pub fn incremental_vacuum(conn: &Connection, max_pages: u32) -> rusqlite::Result<u32> {
+    if max_pages == 0 {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+            Some("incremental vacuum must free at least one page".into()),
+        ));
+    }
+    let free_pages = || conn.pragma_query_value(None, "freelist_count", |row| row.get::<_, u32>(0));
+    let before = free_pages()?;
+    // the pragma returns a row per freed page, which must be stepped through for it to run
+    let mut statement = conn.prepare(&format!("PRAGMA incremental_vacuum({})", max_pages))?;
+    let mut rows = statement.query([])?;
+    while rows.next()?.is_some() {}
+    Ok(before.saturating_sub(free_pages()?))
+}
+
+/// Summarize the database of `conn` as a JSON object, for diagnostics such as a `--db-info`
+/// command.
+///
+/// The object has the version of each schema recorded in the
+/// [schemas table](super::SCHEMAS_TABLE_NAME) under `schemas`, the `page_size`, `page_count`
+/// and `journal_mode` of the database, and the number of rows of each `bdk_`-prefixed table
+/// under `tables`. It contains no wallet data, so it can be shared with support. Unlike
+/// [`storage_stats`], this never checkpoints the write-ahead log.
+#[cfg(feature = "json")]
+pub fn database_info(conn: &Connection) -> rusqlite::Result<String> {
+    use alloc::string::ToString;
+    let table_names = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name LIKE 'bdk\\_%' ESCAPE '\\' ORDER BY name")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut schemas = crate::collections::BTreeMap::<String, u32>::new();
+    if table_names
+        .iter()
+        .any(|table_name| table_name == super::SCHEMAS_TABLE_NAME)
+    {
+        let mut statement = conn.prepare(&format!(
+            "SELECT name, version FROM {}",
+            super::SCHEMAS_TABLE_NAME
+        ))?;
+        let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        for row in rows {
+            let (name, version) = row?;
+            schemas.insert(name, version);
+        }
+    }
+    let mut tables = crate::collections::BTreeMap::<String, u64>::new();
+    for table_name in table_names {
+        let row_count = conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM \"{}\"",
+                table_name.replace('"', "\"\"")
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        tables.insert(table_name, row_count);
+    }
+    let info = serde_json::json!({
+        "schemas": schemas,
+        "page_size": conn.pragma_query_value(None, "page_size", |row| row.get::<_, u32>(0))?,
+        "page_count": conn.pragma_query_value(None, "page_count", |row| row.get::<_, u64>(0))?,
+        "journal_mode": conn.pragma_query_value(None, "journal_mode", |row| row.get::<_, String>(0))?,
+        "tables": tables,
+    });
+    Ok(info.to_string())
+}
+
+/// Write a compacted copy of the database of `conn` to a new file at `path` (`VACUUM INTO`).
+///
+/// The copy is a consistent snapshot and, unlike copying the files of a live database (and its
+/// `-wal` file) on the filesystem, is safe while other connections write. Readers are not blocked.
+/// SQLite refuses to overwrite: this fails if `path` exists and is not empty, so remove an old
+/// backup first. It also fails within a transaction, and if `path` is not valid UTF-8.
+pub fn backup_to(conn: &Connection, path: &std::path::Path) -> rusqlite::Result<()> {
+    let path = path.to_str().ok_or_else(|| {
+        rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+            Some(format!("backup path {:?} is not valid UTF-8", path)),
+        )
+    })?;
+    conn.execute("VACUUM INTO ?1", [path])?;
+    Ok(())
+}
+
+/// Copy the database of `src` into `dst` with SQLite's online backup API, `step_pages` pages at a
+/// time.
+///
+/// Unlike [`backup_to`], this works for in-memory sources and destinations, and copies in steps so
+/// that very large databases can be backed up while reporting progress. `progress` is called
+/// after every step with the number of remaining and total pages. If another connection writes to
+/// the source between steps, the backup restarts. A step that finds the database locked is retried
+/// after a short pause, and the backup fails with `SQLITE_BUSY` after
+/// [`ONLINE_BACKUP_MAX_RETRIES`] retries in a row. The previous content of `dst` is replaced.
+#[cfg(feature = "backup")]
+pub fn online_backup(
+    src: &Connection,
+    dst: &mut Connection,
+    step_pages: u32,
+    mut progress: impl FnMut(rusqlite::backup::Progress),
+) -> rusqlite::Result<()> {
+    use rusqlite::backup::{Backup, StepResult};
+    let step_pages = match i32::try_from(step_pages) {
+        Ok(0) => {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE),
+                Some("backup steps must copy at least one page".into()),
+            ))
+        }
+        Ok(step_pages) => step_pages,
+        Err(_) => i32::MAX,
+    };
+    let backup = Backup::new(src, dst)?;
+    let mut retries = 0;
+    loop {
+        match backup.step(step_pages)? {
+            StepResult::Done => {
+                progress(backup.progress());
+                return Ok(());
+            }
+            StepResult::More => {
+                retries = 0;
+                progress(backup.progress());
+            }
+            _ if retries == ONLINE_BACKUP_MAX_RETRIES => {
+                return Err(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+                    Some("backup gave up, the database stayed locked".into()),
+                ))
+            }
+            _ => {
+                retries += 1;
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        }
+    }
+}
+
+/// Number of times in a row that [`online_backup`] retries a step that finds the database locked,
+/// about one second in total.
+#[cfg(feature = "backup")]
+pub const ONLINE_BACKUP_MAX_RETRIES: u32 = 100;
+
+/// Error of a database operation that can be interrupted with a [`rusqlite::InterruptHandle`].
+///
+/// A long-running load (e.g. a `from_sqlite` call on a large database) can be cancelled from
+/// another thread by obtaining a handle with [`Connection::get_interrupt_handle`] before starting
+/// and calling [`interrupt`](rusqlite::InterruptHandle::interrupt) on it. Mapping the result
+/// with [`InterruptibleError::from`] distinguishes the cancellation from database failures. Any
+/// partially loaded data is discarded.
+#[derive(Debug)]
+pub enum InterruptibleError {
+    /// The operation was interrupted.
+    Interrupted,
+    /// The operation failed for another reason.
+    Sqlite(rusqlite::Error),
+}
+
+impl From<rusqlite::Error> for InterruptibleError {
+    fn from(err: rusqlite::Error) -> Self {
+        match err.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::OperationInterrupted) => Self::Interrupted,
+            _ => Self::Sqlite(err),
+        }
+    }
+}
+
+impl fmt::Display for InterruptibleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Interrupted => write!(f, "database operation was interrupted"),
+            Self::Sqlite(err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+impl std::error::Error for InterruptibleError {}
+
+/// Reports statements that take longer than a threshold to run.
+///
+/// There is a single logger for the whole process, set with [`set_slow_query_logger`].
+#[cfg(feature = "trace")]
+#[derive(Debug, Clone, Copy)]
+pub struct SlowQueryLogger {
+    /// Statements that run for at least this long are logged.
+    pub threshold: core::time::Duration,
+    /// Called with the SQL and run time of each slow statement.
+    pub log: fn(&str, core::time::Duration),
+}
+
+#[cfg(feature = "trace")]
+static SLOW_QUERY_LOGGER: std::sync::Mutex<Option<SlowQueryLogger>> = std::sync::Mutex::new(None);
+
+/// Set the process-wide logger of slow statements, or remove it with `None`.
+///
+/// SQLite's profile callback is only accepted by rusqlite as a plain function pointer, so it
+/// can't carry per-connection state. The logger is therefore shared: it applies to every
+/// connection on which [`enable_slow_query_log`] was called, including those of other wallets in
+/// the same process.
+#[cfg(feature = "trace")]
+pub fn set_slow_query_logger(logger: Option<SlowQueryLogger>) {
+    *SLOW_QUERY_LOGGER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = logger;
+}
+
+/// Report the statements run on `conn` to the logger set with [`set_slow_query_logger`].
+///
+/// This is a low-effort way to find the expensive query of a slow load. Statements are only
+/// timed while a logger is set. Stop reporting for `conn` with [`Connection::profile`] and
+/// `None`.
+#[cfg(feature = "trace")]
+pub fn enable_slow_query_log(conn: &mut Connection) {
+    fn profile(sql: &str, duration: core::time::Duration) {
+        let logger = *SLOW_QUERY_LOGGER
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(logger) = logger {
+            if duration >= logger.threshold {
+                (logger.log)(sql, duration);
+            }
+        }
+    }
+    conn.profile(Some(profile));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rusqlite_impl::Impl;
+    use crate::tx_graph;
+    use crate::ConfirmationBlockTime;
+    use bdk_testenv::{anyhow, hash};
+    use rusqlite::named_params;
+
+    #[test]
+    fn ephemeral_options_disable_durability() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let conn = Connection::open(temp_dir.path().join("cache.sqlite"))?;
+        ConnectionOptions {
+            ephemeral: true,
+            ..Default::default()
+        }
+        .apply(&conn)?;
+        let journal_mode: String =
+            conn.pragma_query_value(None, "journal_mode", |row| row.get(0))?;
+        let synchronous: u32 = conn.pragma_query_value(None, "synchronous", |row| row.get(0))?;
+        assert_eq!(journal_mode, "memory");
+        assert_eq!(synchronous, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn wal_option_relaxes_synchronous() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let conn = Connection::open(temp_dir.path().join("wal.sqlite"))?;
+        ConnectionOptions {
+            wal: true,
+            ..Default::default()
+        }
+        .apply(&conn)?;
+        let journal_mode: String =
+            conn.pragma_query_value(None, "journal_mode", |row| row.get(0))?;
+        let synchronous: u32 = conn.pragma_query_value(None, "synchronous", |row| row.get(0))?;
+        assert_eq!(journal_mode, "wal");
+        assert_eq!(synchronous, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn sizing_options_are_applied_before_tables_are_created() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let temp_dir = tempfile::tempdir()?;
+        let mut conn = Connection::open(temp_dir.path().join("sized.sqlite"))?;
+        let options = ConnectionOptions {
+            page_size: Some(8192),
+            cache_size: Some(-4096),
+            mmap_size: Some(1 << 20),
+            ..Default::default()
+        };
+        options.apply(&conn)?;
+        {
+            let db_tx = conn.transaction()?;
+            ChangeSet::init_sqlite_tables(&db_tx)?;
+            db_tx.commit()?;
+        }
+        let page_size: u32 = conn.pragma_query_value(None, "page_size", |row| row.get(0))?;
+        let cache_size: i64 = conn.pragma_query_value(None, "cache_size", |row| row.get(0))?;
+        assert_eq!(page_size, 8192);
+        assert_eq!(cache_size, -4096);
+
+        for page_size in [256, 1000, 131072] {
+            let options = ConnectionOptions {
+                page_size: Some(page_size),
+                ..Default::default()
+            };
+            assert!(
+                options.apply(&conn).is_err(),
+                "{} must be rejected",
+                page_size
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn incremental_vacuum_frees_bounded_pages() -> anyhow::Result<()> {
+        let options = ConnectionOptions {
+            incremental_auto_vacuum: true,
+            ..Default::default()
+        };
+        let conn = Connection::open_in_memory()?;
+        options.apply(&conn)?;
+        conn.execute_batch(
+            "CREATE TABLE test_churn ( data BLOB ); \
+            WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i+1 FROM n WHERE i < 20) \
+            INSERT INTO test_churn SELECT randomblob(4096) FROM n; \
+            DELETE FROM test_churn",
+        )?;
+        let free_pages =
+            || conn.pragma_query_value(None, "freelist_count", |row| row.get::<_, u32>(0));
+        let before = free_pages()?;
+        assert!(before > 5, "deleting must free pages");
+
+        assert!(incremental_vacuum(&conn, 0).is_err());
+        assert_eq!(incremental_vacuum(&conn, 5)?, 5);
+        assert_eq!(free_pages()?, before - 5);
+        assert_eq!(incremental_vacuum(&conn, u32::MAX)?, before - 5);
+        assert_eq!(free_pages()?, 0);
+        // applying again is fine once the mode is set
+        options.apply(&conn)?;
+
+        // the mode can't be switched once tables exist
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch("CREATE TABLE test_existing ( id INTEGER )")?;
+        assert!(options.apply(&conn).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn secure_delete_zeroes_deleted_content() -> anyhow::Result<()> {
+        let secret = "bc1qforgottenaddressthatmustnotberecoverable".repeat(4);
+        let temp_dir = tempfile::tempdir()?;
+        for secure_delete in [false, true] {
+            let path = temp_dir
+                .path()
+                .join(format!("secure_delete_{}.sqlite", secure_delete));
+            let conn = Connection::open(&path)?;
+            ConnectionOptions {
+                wal: true,
+                secure_delete,
+                ..Default::default()
+            }
+            .apply(&conn)?;
+            conn.execute_batch("CREATE TABLE labels ( id INTEGER PRIMARY KEY, label TEXT )")?;
+            conn.execute("INSERT INTO labels(label) VALUES(?1), ('kept')", [&secret])?;
+            conn.execute("DELETE FROM labels WHERE label = ?1", [&secret])?;
+            wal_checkpoint(&conn, WalCheckpointMode::Truncate)?;
+
+            let file = std::fs::read(&path)?;
+            let recoverable = file
+                .windows(secret.len())
+                .any(|window| window == secret.as_bytes());
+            assert_eq!(recoverable, !secure_delete);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn slow_queries_are_logged() -> anyhow::Result<()> {
+        use core::time::Duration;
+        static LOGGED: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+        fn log(sql: &str, _: Duration) {
+            LOGGED.lock().unwrap().push(sql.into());
+        }
+
+        let mut conn = Connection::open_in_memory()?;
+        enable_slow_query_log(&mut conn);
+        conn.query_row("SELECT 'no logger'", [], |_| Ok(()))?;
+        set_slow_query_logger(Some(SlowQueryLogger {
+            threshold: Duration::ZERO,
+            log,
+        }));
+        conn.query_row("SELECT 'slow'", [], |_| Ok(()))?;
+        set_slow_query_logger(Some(SlowQueryLogger {
+            threshold: Duration::from_secs(3600),
+            log,
+        }));
+        conn.query_row("SELECT 'fast'", [], |_| Ok(()))?;
+        set_slow_query_logger(Some(SlowQueryLogger {
+            threshold: Duration::ZERO,
+            log,
+        }));
+        conn.profile(None);
+        conn.query_row("SELECT 'untraced'", [], |_| Ok(()))?;
+        set_slow_query_logger(None);
+
+        assert_eq!(*LOGGED.lock().unwrap(), vec![String::from("SELECT 'slow'")]);
+        Ok(())
+    }
+
+    #[test]
+    fn warm_cache_reads_given_tables() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let mut conn = Connection::open_in_memory()?;
+        {
+            let db_tx = conn.transaction()?;
+            ChangeSet::init_sqlite_tables(&db_tx)?;
+            db_tx.execute(
+                &format!(
+                    "INSERT INTO {}(txid, raw_tx) VALUES(:txid, zeroblob(100000))",
+                    ChangeSet::TXS_TABLE_NAME
+                ),
+                named_params! { ":txid": Impl::<bitcoin::Txid>(hash!("large")) },
+            )?;
+            db_tx.commit()?;
+        }
+        warm_cache(&conn, ChangeSet::TABLE_NAMES)?;
+        assert!(warm_cache(&conn, &["no_such_table"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn backup_to_writes_consistent_copy() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let temp_dir = tempfile::tempdir()?;
+        let mut conn = Connection::open(temp_dir.path().join("live.sqlite"))?;
+        ConnectionOptions {
+            wal: true,
+            ..Default::default()
+        }
+        .apply(&conn)?;
+        let changeset = ChangeSet {
+            last_seen: [(hash!("tx"), 100)].into(),
+            ..Default::default()
+        };
+        {
+            let db_tx = conn.transaction()?;
+            ChangeSet::init_sqlite_tables(&db_tx)?;
+            changeset.persist_to_sqlite(&db_tx)?;
+            db_tx.commit()?;
+        }
+
+        let backup_path = temp_dir.path().join("backup.sqlite");
+        backup_to(&conn, &backup_path)?;
+        assert!(
+            backup_to(&conn, &backup_path).is_err(),
+            "must not overwrite"
+        );
+
+        let mut backup = Connection::open(&backup_path)?;
+        let db_tx = backup.transaction()?;
+        assert_eq!(ChangeSet::from_sqlite(&db_tx)?, changeset);
+        Ok(())
+    }
+
+    #[cfg(feature = "backup")]
+    #[test]
+    fn online_backup_reports_progress() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let mut src = Connection::open_in_memory()?;
+        let changeset = ChangeSet {
+            last_seen: (0..200_u32)
+                .map(|i| {
+                    use bitcoin::hashes::Hash;
+                    (bitcoin::Txid::hash(&i.to_le_bytes()), u64::from(i))
+                })
+                .collect(),
+            ..Default::default()
+        };
+        {
+            let db_tx = src.transaction()?;
+            ChangeSet::init_sqlite_tables(&db_tx)?;
+            changeset.persist_to_sqlite(&db_tx)?;
+            db_tx.commit()?;
+        }
+
+        let mut dst = Connection::open_in_memory()?;
+        assert!(online_backup(&src, &mut dst, 0, |_| {}).is_err());
+        let mut reports = Vec::new();
+        online_backup(&src, &mut dst, 1, |progress| reports.push(progress))?;
+        assert!(reports.len() > 1, "must copy in steps");
+        let last = reports.last().expect("must report progress");
+        assert_eq!(last.remaining, 0);
+        assert!(last.pagecount > 1);
+
+        let db_tx = dst.transaction()?;
+        assert_eq!(ChangeSet::from_sqlite(&db_tx)?, changeset);
+        Ok(())
+    }
+
+    #[cfg(feature = "backup")]
+    #[test]
+    fn online_backup_gives_up_on_a_locked_database() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("locked.sqlite");
+        let mut writer = Connection::open(&path)?;
+        writer.execute("CREATE TABLE t(x)", ())?;
+        let src = Connection::open(&path)?;
+        src.busy_timeout(core::time::Duration::ZERO)?;
+        let _lock = writer.transaction_with_behavior(rusqlite::TransactionBehavior::Exclusive)?;
+
+        let mut dst = Connection::open_in_memory()?;
+        match online_backup(&src, &mut dst, 1, |_| {}) {
+            Err(rusqlite::Error::SqliteFailure(e, _)) => {
+                assert_eq!(e.code, rusqlite::ErrorCode::DatabaseBusy)
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn enable_foreign_keys_rejects_existing_violations() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let mut conn = Connection::open_in_memory()?;
+        {
+            let db_tx = conn.transaction()?;
+            ChangeSet::init_sqlite_tables(&db_tx)?;
+            db_tx.commit()?;
+        }
+        enable_foreign_keys(&conn)?;
+        let foreign_keys_on = |conn: &Connection| {
+            conn.pragma_query_value(None, "foreign_keys", |row| row.get::<_, bool>(0))
+        };
+        assert!(foreign_keys_on(&conn)?);
+
+        // an anchor without a tx row can only be inserted while enforcement is off
+        conn.pragma_update(None, "foreign_keys", false)?;
+        conn.execute(
+            &format!(
+                "INSERT INTO {}(txid, block_height, block_hash) VALUES(:txid, 1, :hash)",
+                ChangeSet::ANCHORS_TABLE_NAME
+            ),
+            named_params! {
+                ":txid": Impl::<bitcoin::Txid>(hash!("tx")),
+                ":hash": Impl::<bitcoin::BlockHash>(hash!("block")),
+            },
+        )?;
+        assert!(enable_foreign_keys(&conn).is_err());
+        assert!(!foreign_keys_on(&conn)?);
+        Ok(())
+    }
+
+    #[test]
+    fn read_rejects_writes() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let mut conn = Connection::open_in_memory()?;
+        {
+            let db_tx = conn.transaction()?;
+            ChangeSet::init_sqlite_tables(&db_tx)?;
+            db_tx.commit()?;
+        }
+
+        let loaded = read_only(&mut conn, ChangeSet::from_sqlite)?;
+        assert_eq!(loaded, ChangeSet::default());
+
+        let res = read_only(&mut conn, |db_tx| {
+            db_tx.execute(&format!("DELETE FROM {}", ChangeSet::TXS_TABLE_NAME), ())
+        });
+        assert_eq!(
+            res.expect_err("write must fail").sqlite_error_code(),
+            Some(rusqlite::ErrorCode::ReadOnly)
+        );
+
+        // the connection is writable again afterwards
+        conn.execute(&format!("DELETE FROM {}", ChangeSet::TXS_TABLE_NAME), ())?;
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            read_only(&mut conn, |_| -> rusqlite::Result<()> {
+                panic!("read failed")
+            })
+        }));
+        assert!(panicked.is_err());
+        conn.execute(&format!("DELETE FROM {}", ChangeSet::TXS_TABLE_NAME), ())?;
+        Ok(())
+    }
+
+    #[test]
+    fn wal_checkpoint_reports_frames() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let temp_dir = tempfile::tempdir()?;
+        let mut conn = Connection::open(temp_dir.path().join("wal.sqlite"))?;
+        assert!(wal_checkpoint(&conn, WalCheckpointMode::Passive).is_err());
+
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        conn.pragma_update(None, "wal_autocheckpoint", 0)?;
+        {
+            let db_tx = conn.transaction()?;
+            ChangeSet::init_sqlite_tables(&db_tx)?;
+            db_tx.commit()?;
+        }
+        let (log_frames, checkpointed_frames) = wal_checkpoint(&conn, WalCheckpointMode::Full)?;
+        assert!(log_frames > 0);
+        assert_eq!(checkpointed_frames, log_frames);
+
+        wal_checkpoint(&conn, WalCheckpointMode::Truncate)?;
+        let wal_len = std::fs::metadata(temp_dir.path().join("wal.sqlite-wal"))?.len();
+        assert_eq!(wal_len, 0);
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn database_info_summarizes_schemas_and_tables() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let txid: bitcoin::Txid = hash!("tx");
+        let mut conn = Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        ChangeSet::init_sqlite_tables(&db_tx)?;
+        db_tx.execute(
+            &format!(
+                "INSERT INTO {}(txid, last_seen) VALUES(:txid, 1)",
+                ChangeSet::TXS_TABLE_NAME
+            ),
+            named_params! { ":txid": Impl(txid) },
+        )?;
+        db_tx.execute("CREATE TABLE app_table ( id INTEGER )", ())?;
+        db_tx.commit()?;
+
+        let info: serde_json::Value = serde_json::from_str(&database_info(&conn)?)?;
+        assert_eq!(
+            info["schemas"],
+            serde_json::json!({ ChangeSet::SCHEMA_NAME: ChangeSet::SCHEMA_VERSION })
+        );
+        assert_eq!(info["journal_mode"], "memory");
+        assert!(info["page_size"].as_u64().expect("must be a number") > 0);
+        assert_eq!(
+            info["tables"],
+            serde_json::json!({
+                "bdk_anchors": 0,
+                "bdk_schemas": 1,
+                "bdk_txouts": 0,
+                "bdk_txs": 1,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn wal_is_truncated_above_size_cap() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let temp_dir = tempfile::tempdir()?;
+        let wal_path = temp_dir.path().join("capped.sqlite-wal");
+        let mut conn = Connection::open(temp_dir.path().join("capped.sqlite"))?;
+        assert!(truncate_wal_above(&conn, 0).is_err());
+
+        ConnectionOptions {
+            wal: true,
+            wal_autocheckpoint_pages: Some(0),
+            ..Default::default()
+        }
+        .apply(&conn)?;
+        let autocheckpoint: u32 =
+            conn.pragma_query_value(None, "wal_autocheckpoint", |row| row.get(0))?;
+        assert_eq!(autocheckpoint, 0);
+        {
+            let db_tx = conn.transaction()?;
+            ChangeSet::init_sqlite_tables(&db_tx)?;
+            db_tx.commit()?;
+        }
+        let wal_size = storage_stats(&conn)?
+            .wal_size()
+            .expect("must be in WAL mode");
+        assert_eq!(std::fs::metadata(&wal_path)?.len(), wal_size);
+
+        assert!(!truncate_wal_above(&conn, wal_size)?);
+        assert!(truncate_wal_above(&conn, wal_size - 1)?);
+        assert_eq!(std::fs::metadata(&wal_path)?.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn auto_checkpoint_runs_in_background() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let interval = std::time::Duration::from_millis(10);
+        assert!(spawn_auto_checkpoint(&Connection::open_in_memory()?, interval).is_err());
+
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("auto.sqlite");
+        let mut conn = Connection::open(&path)?;
+        // a rollback journal can't be checkpointed
+        assert!(spawn_auto_checkpoint(&conn, interval).is_err());
+        ConnectionOptions {
+            wal: true,
+            ..Default::default()
+        }
+        .apply(&conn)?;
+        conn.pragma_update(None, "wal_autocheckpoint", 0)?;
+        let handle = spawn_auto_checkpoint(&conn, interval)?;
+        {
+            let db_tx = conn.transaction()?;
+            ChangeSet::init_sqlite_tables(&db_tx)?;
+            db_tx.commit()?;
+        }
+
+        // the main database file only grows to its full size once the log is checkpointed
+        let page_size: u64 = conn.pragma_query_value(None, "page_size", |row| row.get(0))?;
+        let page_count: u64 = conn.pragma_query_value(None, "page_count", |row| row.get(0))?;
+        let full_size = page_size * page_count;
+        let start = std::time::Instant::now();
+        while std::fs::metadata(&path)?.len() < full_size {
+            assert!(
+                start.elapsed() < std::time::Duration::from_secs(10),
+                "log must be checkpointed in the background"
+            );
+            std::thread::sleep(interval);
+        }
+        assert!(handle.take_errors().is_empty());
+        drop(handle);
+        Ok(())
+    }
+
+    #[test]
+    fn storage_stats_report_database_and_wal_size() -> anyhow::Result<()> {
+        type ChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+        let memory_conn = Connection::open_in_memory()?;
+        let stats = storage_stats(&memory_conn)?;
+        assert_eq!(stats.wal_frames, None);
+        assert_eq!(stats.wal_size(), None);
+
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("stats.sqlite");
+        let mut conn = Connection::open(&path)?;
+        ConnectionOptions {
+            wal: true,
+            ..Default::default()
+        }
+        .apply(&conn)?;
+        conn.pragma_update(None, "wal_autocheckpoint", 0)?;
+        {
+            let db_tx = conn.transaction()?;
+            ChangeSet::init_sqlite_tables(&db_tx)?;
+            db_tx.commit()?;
+        }
+        let stats = storage_stats(&conn)?;
+        assert!(stats.page_count > 0);
+        assert_eq!(
+            stats.wal_size(),
+            Some(std::fs::metadata(path.with_extension("sqlite-wal"))?.len())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn interrupted_query_is_reported_as_interrupted() -> anyhow::Result<()> {
+        let conn = Connection::open_in_memory()?;
+        let handle = conn.get_interrupt_handle();
+        let interrupter = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            handle.interrupt();
+        });
+        let res = conn
+            .query_row(
+                "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c LIMIT 1000000000) \
+                SELECT count(*) FROM c",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .map_err(InterruptibleError::from);
+        interrupter.join().expect("must join");
+        assert!(matches!(res, Err(InterruptibleError::Interrupted)));
+        Ok(())
+    }
+}
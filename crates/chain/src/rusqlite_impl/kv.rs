@@ -0,0 +1,121 @@
+//! A key-value table for application settings.
+//!
+//! Wallet applications often need to store a handful of typed settings (such as a gap limit or a
+//! label) alongside the wallet data. The table of this module maps each key to a single value of
+//! any SQLite type. Values can be accessed individually with [`get`] and [`set`], or loaded and
+//! persisted as a [`ChangeSet`] where the last write wins for each key.
+
+use super::{get_sql, migrate_schema};
+use crate::collections::BTreeMap;
+use alloc::{format, string::String};
+use rusqlite::types::{FromSql, ToSql, Value};
+use rusqlite::{named_params, OptionalExtension, Transaction};
+
+/// Schema name for the key-value table.
+pub const SCHEMA_NAME: &str = "bdk_kv";
+/// Name of the key-value table.
+pub const TABLE_NAME: &str = "bdk_kv";
+
+/// Changes to the key-value table.
+///
+/// Merging keeps the value of the most recent write for each key.
+pub type ChangeSet = BTreeMap<String, Value>;
+
+/// Get v0 of the sqlite schema for the key-value table.
+pub fn schema_v0() -> String {
+    format!(
+        "CREATE TABLE {} ( \
+        key TEXT PRIMARY KEY NOT NULL, \
+        value ANY \
+        ) STRICT",
+        TABLE_NAME,
+    )
+}
+
+/// Initialize the sqlite table for key-value settings.
+pub fn init_sqlite_tables(db_tx: &Transaction) -> rusqlite::Result<()> {
+    migrate_schema(db_tx, SCHEMA_NAME, &[&schema_v0()])
+}
+
+/// Load all key-value pairs.
+pub fn from_sqlite(db_tx: &Transaction) -> rusqlite::Result<ChangeSet> {
+    let mut statement = db_tx.prepare(&format!("SELECT key, value FROM {}", TABLE_NAME))?;
+    let rows = statement.query_map([], |row| {
+        Ok((
+            get_sql::<String>(row, "key")?,
+            get_sql::<Value>(row, "value")?,
+        ))
+    })?;
+    rows.collect()
+}
+
+/// Persist all key-value pairs of `changeset`, overwriting existing values.
+pub fn persist_to_sqlite(db_tx: &Transaction, changeset: &ChangeSet) -> rusqlite::Result<()> {
+    for (key, value) in changeset {
+        set(db_tx, key, value)?;
+    }
+    Ok(())
+}
+
+/// Get the value stored under `key`, converted to `T`.
+///
+/// Returns `None` if no value is stored under `key`.
+pub fn get<T: FromSql>(db_tx: &Transaction, key: &str) -> rusqlite::Result<Option<T>> {
+    db_tx
+        .prepare_cached(&format!("SELECT value FROM {} WHERE key=:key", TABLE_NAME))?
+        .query_row(named_params! { ":key": key }, |row| {
+            get_sql::<T>(row, "value")
+        })
+        .optional()
+}
+
+/// Store `value` under `key`, overwriting any existing value.
+pub fn set<T: ToSql + ?Sized>(db_tx: &Transaction, key: &str, value: &T) -> rusqlite::Result<()> {
+    db_tx
+        .prepare_cached(&format!(
+            "REPLACE INTO {}(key, value) VALUES(:key, :value)",
+            TABLE_NAME
+        ))?
+        .execute(named_params! { ":key": key, ":value": value })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rusqlite_impl::Impl;
+    use crate::Merge;
+    use alloc::borrow::ToOwned;
+    use bdk_testenv::anyhow;
+
+    #[test]
+    fn typed_settings_round_trip() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        init_sqlite_tables(&db_tx)?;
+        assert_eq!(get::<u32>(&db_tx, "gap_limit")?, None);
+
+        set(&db_tx, "gap_limit", &20_u32)?;
+        set(&db_tx, "network", &Impl(bitcoin::Network::Signet))?;
+        set(&db_tx, "gap_limit", &25_u32)?;
+        assert_eq!(get::<u32>(&db_tx, "gap_limit")?, Some(25));
+        assert_eq!(
+            get::<Impl<bitcoin::Network>>(&db_tx, "network")?.map(Impl::into_inner),
+            Some(bitcoin::Network::Signet)
+        );
+
+        let mut changeset = from_sqlite(&db_tx)?;
+        Merge::merge(
+            &mut changeset,
+            [("label".to_owned(), Value::Text("savings".to_owned()))].into(),
+        );
+        Merge::merge(
+            &mut changeset,
+            [("gap_limit".to_owned(), Value::Integer(30))].into(),
+        );
+        persist_to_sqlite(&db_tx, &changeset)?;
+        assert_eq!(get::<u32>(&db_tx, "gap_limit")?, Some(30));
+        assert_eq!(from_sqlite(&db_tx)?, changeset);
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+//! Storage of [BIP-329] labels of transactions, addresses and outputs.
+//!
+//! Each label is keyed by the kind of thing it refers to (`ref_type`) and its identifier
+//! (`ref_id`). Txids and script pubkeys are stored with their [`Impl`] conversions, and outpoints
+//! as `txid:vout` text like in BIP-329 exports.
+//!
+//! [BIP-329]: https://github.com/bitcoin/bips/blob/master/bip-0329.mediawiki
+
+use super::{get_sql, migrate_schema, Impl};
+use crate::collections::BTreeMap;
+use crate::Merge;
+use alloc::{boxed::Box, format, string::String, string::ToString};
+use bitcoin::{OutPoint, ScriptBuf, Txid};
+use core::str::FromStr;
+use rusqlite::types::{FromSql, Value, ValueRef};
+use rusqlite::{named_params, OptionalExtension, Transaction};
+
+/// Schema name for labels.
+pub const SCHEMA_NAME: &str = "bdk_labels";
+/// Name of the table that stores labels.
+pub const TABLE_NAME: &str = "bdk_labels";
+
+/// What a label refers to.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LabelRef {
+    /// A transaction (BIP-329 type `tx`).
+    Tx(Txid),
+    /// An address, identified by its script pubkey (BIP-329 type `addr`).
+    Address(ScriptBuf),
+    /// A transaction input, identified by the outpoint it spends (BIP-329 type `input`).
+    Input(OutPoint),
+    /// A transaction output (BIP-329 type `output`).
+    Output(OutPoint),
+}
+
+impl LabelRef {
+    /// The BIP-329 type of the label.
+    pub fn ref_type(&self) -> &'static str {
+        match self {
+            LabelRef::Tx(_) => "tx",
+            LabelRef::Address(_) => "addr",
+            LabelRef::Input(_) => "input",
+            LabelRef::Output(_) => "output",
+        }
+    }
+
+    fn ref_id(&self) -> Value {
+        match self {
+            LabelRef::Tx(txid) => Value::Text(txid.to_string()),
+            LabelRef::Address(script) => Value::Blob(script.to_bytes()),
+            LabelRef::Input(outpoint) | LabelRef::Output(outpoint) => {
+                Value::Text(outpoint.to_string())
+            }
+        }
+    }
+
+    fn from_columns(ref_type: &str, ref_id: ValueRef<'_>) -> rusqlite::Result<Self> {
+        let conversion_error = |err: Box<dyn std::error::Error + Send + Sync>| {
+            rusqlite::Error::FromSqlConversionFailure(1, ref_id.data_type(), err)
+        };
+        let outpoint = || -> rusqlite::Result<OutPoint> {
+            let text = ref_id
+                .as_str()
+                .map_err(|err| conversion_error(Box::new(err)))?;
+            OutPoint::from_str(text).map_err(|err| conversion_error(Box::new(err)))
+        };
+        Ok(match ref_type {
+            "tx" => LabelRef::Tx(
+                Impl::<Txid>::column_result(ref_id)
+                    .map_err(|err| conversion_error(Box::new(err)))?
+                    .0,
+            ),
+            "addr" => LabelRef::Address(
+                Impl::<ScriptBuf>::column_result(ref_id)
+                    .map_err(|err| conversion_error(Box::new(err)))?
+                    .0,
+            ),
+            "input" => LabelRef::Input(outpoint()?),
+            "output" => LabelRef::Output(outpoint()?),
+            unknown => {
+                return Err(conversion_error(
+                    format!("unknown label type `{}`", unknown).into(),
+                ))
+            }
+        })
+    }
+}
+
+/// Changes to the stored labels.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeSet {
+    /// Label of each reference.
+    pub labels: BTreeMap<LabelRef, String>,
+}
+
+impl Merge for ChangeSet {
+    /// Merging is last-write-wins per reference.
+    fn merge(&mut self, other: Self) {
+        self.labels.extend(other.labels);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+}
+
+/// Get v0 of the sqlite schema for labels.
+pub fn schema_v0() -> String {
+    format!(
+        "CREATE TABLE {} ( \
+        ref_type TEXT NOT NULL, \
+        ref_id ANY NOT NULL, \
+        label TEXT NOT NULL, \
+        PRIMARY KEY (ref_type, ref_id) \
+        ) STRICT",
+        TABLE_NAME,
+    )
+}
+
+/// Initialize the sqlite table for labels.
+pub fn init_sqlite_tables(db_tx: &Transaction) -> rusqlite::Result<()> {
+    migrate_schema(db_tx, SCHEMA_NAME, &[&schema_v0()])
+}
+
+/// Get the label of `label_ref`, if any.
+pub fn label(db_tx: &Transaction, label_ref: &LabelRef) -> rusqlite::Result<Option<String>> {
+    db_tx
+        .prepare_cached(&format!(
+            "SELECT label FROM {} WHERE ref_type=:ref_type AND ref_id=:ref_id",
+            TABLE_NAME,
+        ))?
+        .query_row(
+            named_params! { ":ref_type": label_ref.ref_type(), ":ref_id": label_ref.ref_id() },
+            |row| get_sql::<String>(row, "label"),
+        )
+        .optional()
+}
+
+/// Set the label of `label_ref`, replacing any previous label.
+pub fn set_label(db_tx: &Transaction, label_ref: &LabelRef, label: &str) -> rusqlite::Result<()> {
+    db_tx
+        .prepare_cached(&format!(
+            "REPLACE INTO {}(ref_type, ref_id, label) VALUES(:ref_type, :ref_id, :label)",
+            TABLE_NAME,
+        ))?
+        .execute(named_params! {
+            ":ref_type": label_ref.ref_type(),
+            ":ref_id": label_ref.ref_id(),
+            ":label": label,
+        })?;
+    Ok(())
+}
+
+/// Get the label of the transaction `txid`, if any.
+pub fn label_for_tx(db_tx: &Transaction, txid: Txid) -> rusqlite::Result<Option<String>> {
+    label(db_tx, &LabelRef::Tx(txid))
+}
+
+/// Set the label of the transaction `txid`.
+pub fn set_label_for_tx(db_tx: &Transaction, txid: Txid, label: &str) -> rusqlite::Result<()> {
+    set_label(db_tx, &LabelRef::Tx(txid), label)
+}
+
+/// Get the label of `address`, if any.
+pub fn label_for_address(
+    db_tx: &Transaction,
+    address: &bitcoin::Address,
+) -> rusqlite::Result<Option<String>> {
+    label(db_tx, &LabelRef::Address(address.script_pubkey()))
+}
+
+/// Set the label of `address`.
+pub fn set_label_for_address(
+    db_tx: &Transaction,
+    address: &bitcoin::Address,
+    label: &str,
+) -> rusqlite::Result<()> {
+    set_label(db_tx, &LabelRef::Address(address.script_pubkey()), label)
+}
+
+/// Load all labels.
+pub fn from_sqlite(db_tx: &Transaction) -> rusqlite::Result<ChangeSet> {
+    let mut statement = db_tx.prepare(&format!(
+        "SELECT ref_type, ref_id, label FROM {}",
+        TABLE_NAME,
+    ))?;
+    let rows = statement.query_map([], |row| {
+        let label_ref =
+            LabelRef::from_columns(&get_sql::<String>(row, "ref_type")?, row.get_ref("ref_id")?)?;
+        Ok((label_ref, get_sql::<String>(row, "label")?))
+    })?;
+    Ok(ChangeSet {
+        labels: rows.collect::<rusqlite::Result<_>>()?,
+    })
+}
+
+/// Persist `changeset`, replacing the stored labels of its references.
+pub fn persist_to_sqlite(db_tx: &Transaction, changeset: &ChangeSet) -> rusqlite::Result<()> {
+    for (label_ref, label) in &changeset.labels {
+        set_label(db_tx, label_ref, label)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bdk_testenv::{anyhow, hash};
+
+    #[test]
+    fn labels_are_last_write_wins() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        init_sqlite_tables(&db_tx)?;
+
+        let txid: Txid = hash!("tx");
+        let address = bitcoin::Address::from_str("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq")?
+            .assume_checked();
+        let outpoint = OutPoint::new(txid, 1);
+
+        let mut merged = ChangeSet {
+            labels: [
+                (LabelRef::Tx(txid), "rent".to_string()),
+                (LabelRef::Output(outpoint), "change".to_string()),
+            ]
+            .into(),
+        };
+        persist_to_sqlite(&db_tx, &merged)?;
+        let update = ChangeSet {
+            labels: [
+                (LabelRef::Tx(txid), "rent (march)".to_string()),
+                (LabelRef::Input(outpoint), "spent change".to_string()),
+            ]
+            .into(),
+        };
+        persist_to_sqlite(&db_tx, &update)?;
+        merged.merge(update);
+        set_label_for_address(&db_tx, &address, "donations")?;
+        merged.labels.insert(
+            LabelRef::Address(address.script_pubkey()),
+            "donations".to_string(),
+        );
+
+        assert_eq!(from_sqlite(&db_tx)?, merged);
+        assert_eq!(label_for_tx(&db_tx, txid)?.as_deref(), Some("rent (march)"));
+        assert_eq!(
+            label_for_address(&db_tx, &address)?.as_deref(),
+            Some("donations")
+        );
+        assert_eq!(label_for_tx(&db_tx, hash!("unlabeled"))?, None);
+        Ok(())
+    }
+}
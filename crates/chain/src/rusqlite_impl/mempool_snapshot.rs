@@ -0,0 +1,102 @@
+//! Storage of a snapshot of the unconfirmed transactions known to the mempool.
+//!
+//! A snapshot is the set of unconfirmed txids with the time each was last seen. Persisting it
+//! separately from the wallet makes it possible to capture and restore the mempool view, e.g. to
+//! reproduce a bug.
+
+use super::{load_last_seen, migrate_schema, timestamps_table_schema, upsert_last_seen};
+use crate::collections::BTreeMap;
+use crate::Merge;
+use alloc::string::String;
+use bitcoin::Txid;
+use rusqlite::Transaction;
+
+/// Schema name for the mempool snapshot.
+pub const SCHEMA_NAME: &str = "bdk_mempool_snapshot";
+/// Name of the table that stores the mempool snapshot.
+pub const TABLE_NAME: &str = "bdk_mempool_snapshot";
+
+/// Changes to the mempool snapshot.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeSet {
+    /// Unix time each unconfirmed transaction was last seen in the mempool.
+    pub last_seen: BTreeMap<Txid, u64>,
+}
+
+impl Merge for ChangeSet {
+    /// Merging keeps the greatest `last_seen` of each txid.
+    fn merge(&mut self, other: Self) {
+        for (txid, last_seen) in other.last_seen {
+            let entry = self.last_seen.entry(txid).or_default();
+            *entry = (*entry).max(last_seen);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.last_seen.is_empty()
+    }
+}
+
+impl FromIterator<(Txid, u64)> for ChangeSet {
+    fn from_iter<I: IntoIterator<Item = (Txid, u64)>>(iter: I) -> Self {
+        let mut changeset = Self::default();
+        for (txid, last_seen) in iter {
+            changeset.merge(Self {
+                last_seen: [(txid, last_seen)].into(),
+            });
+        }
+        changeset
+    }
+}
+
+/// Get v0 of the sqlite schema for the mempool snapshot.
+pub fn schema_v0() -> String {
+    timestamps_table_schema(TABLE_NAME).expect("TABLE_NAME is a valid identifier")
+}
+
+/// Initialize the sqlite table for the mempool snapshot.
+pub fn init_sqlite_tables(db_tx: &Transaction) -> rusqlite::Result<()> {
+    migrate_schema(db_tx, SCHEMA_NAME, &[&schema_v0()])
+}
+
+/// Load the mempool snapshot.
+pub fn from_sqlite(db_tx: &Transaction) -> rusqlite::Result<ChangeSet> {
+    Ok(ChangeSet {
+        last_seen: load_last_seen(db_tx, TABLE_NAME)?,
+    })
+}
+
+/// Persist `changeset` to the mempool snapshot.
+///
+/// Like [`Merge`], the stored `last_seen` of a txid only ever increases.
+pub fn persist_to_sqlite(db_tx: &Transaction, changeset: &ChangeSet) -> rusqlite::Result<()> {
+    for (&txid, &last_seen) in &changeset.last_seen {
+        upsert_last_seen(db_tx, TABLE_NAME, txid, last_seen)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bdk_testenv::{anyhow, hash};
+
+    #[test]
+    fn snapshot_keeps_latest_last_seen() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        init_sqlite_tables(&db_tx)?;
+
+        let (tx_a, tx_b): (Txid, Txid) = (hash!("a"), hash!("b"));
+        let first = ChangeSet::from_iter([(tx_a, 20), (tx_b, 10)]);
+        let second = ChangeSet::from_iter([(tx_a, 15), (tx_b, 30)]);
+        persist_to_sqlite(&db_tx, &first)?;
+        persist_to_sqlite(&db_tx, &second)?;
+
+        let mut merged = first;
+        merged.merge(second);
+        assert_eq!(merged, ChangeSet::from_iter([(tx_a, 20), (tx_b, 30)]));
+        assert_eq!(from_sqlite(&db_tx)?, merged);
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+//! Storage of transactions in normalized `tx`, `txin` and `txout` tables.
+//!
+//! Storing transactions as consensus-encoded BLOBs (as [`tx_graph::ChangeSet`] does) is compact,
+//! but does not allow queries over transaction fields, e.g. finding all transactions spending an
+//! outpoint. The tables of this module store every field in its own column so that they can be
+//! used in `JOIN`s. Loading reconstructs the exact [`Transaction`] and checks that its txid and
+//! wtxid match.
+//!
+//! [`tx_graph::ChangeSet`]: crate::tx_graph::ChangeSet
+
+use super::{get_sql, migrate_schema, Impl};
+use alloc::{format, string::String, vec::Vec};
+use bitcoin::{absolute, transaction, OutPoint, Sequence, Transaction, TxIn, TxOut, Txid, Wtxid};
+use rusqlite::{named_params, OptionalExtension, Transaction as DbTransaction};
+
+/// Schema name for normalized transactions.
+pub const SCHEMA_NAME: &str = "bdk_normalized_tx";
+/// Name of table that stores the transaction-level fields.
+pub const TXS_TABLE_NAME: &str = "bdk_normalized_txs";
+/// Name of table that stores transaction inputs.
+pub const TXINS_TABLE_NAME: &str = "bdk_normalized_txins";
+/// Name of table that stores transaction outputs.
+pub const TXOUTS_TABLE_NAME: &str = "bdk_normalized_txouts";
+
+/// Get v0 of the sqlite schema for normalized transactions.
+pub fn schema_v0() -> String {
+    let create_txs_table = format!(
+        "CREATE TABLE {} ( \
+        txid TEXT PRIMARY KEY NOT NULL, \
+        wtxid TEXT NOT NULL, \
+        version INTEGER NOT NULL, \
+        lock_time INTEGER NOT NULL \
+        ) STRICT",
+        TXS_TABLE_NAME,
+    );
+    let create_txins_table = format!(
+        "CREATE TABLE {} ( \
+        txid TEXT NOT NULL REFERENCES {} (txid), \
+        vin INTEGER NOT NULL, \
+        prev_txid TEXT NOT NULL, \
+        prev_vout INTEGER NOT NULL, \
+        script_sig BLOB NOT NULL, \
+        sequence INTEGER NOT NULL, \
+        witness BLOB NOT NULL, \
+        PRIMARY KEY (txid, vin) \
+        ) STRICT",
+        TXINS_TABLE_NAME, TXS_TABLE_NAME,
+    );
+    let create_txins_index = format!(
+        "CREATE INDEX {0}_prevout ON {0} (prev_txid, prev_vout)",
+        TXINS_TABLE_NAME,
+    );
+    let create_txouts_table = format!(
+        "CREATE TABLE {} ( \
+        txid TEXT NOT NULL REFERENCES {} (txid), \
+        vout INTEGER NOT NULL, \
+        value INTEGER NOT NULL, \
+        script BLOB NOT NULL, \
+        PRIMARY KEY (txid, vout) \
+        ) STRICT",
+        TXOUTS_TABLE_NAME, TXS_TABLE_NAME,
+    );
+    format!("{create_txs_table}; {create_txins_table}; {create_txins_index}; {create_txouts_table}")
+}
+
+/// Initialize sqlite tables for normalized transactions.
+pub fn init_sqlite_tables(db_tx: &DbTransaction) -> rusqlite::Result<()> {
+    migrate_schema(db_tx, SCHEMA_NAME, &[&schema_v0()])
+}
+
+/// Insert `tx` into the normalized tables, replacing any previously stored version of it.
+///
+/// Remember to call [`init_sqlite_tables`] beforehand.
+pub fn insert_tx_normalized(db_tx: &DbTransaction, tx: &Transaction) -> rusqlite::Result<()> {
+    let txid = tx.compute_txid();
+    for table_name in [TXINS_TABLE_NAME, TXOUTS_TABLE_NAME] {
+        db_tx
+            .prepare_cached(&format!("DELETE FROM {} WHERE txid=:txid", table_name))?
+            .execute(named_params! { ":txid": Impl(txid) })?;
+    }
+    db_tx
+        .prepare_cached(&format!(
+            "REPLACE INTO {}(txid, wtxid, version, lock_time) \
+            VALUES(:txid, :wtxid, :version, :lock_time)",
+            TXS_TABLE_NAME,
+        ))?
+        .execute(named_params! {
+            ":txid": Impl(txid),
+            ":wtxid": Impl(tx.compute_wtxid()),
+            ":version": tx.version.0,
+            ":lock_time": tx.lock_time.to_consensus_u32(),
+        })?;
+
+    let mut statement = db_tx.prepare_cached(&format!(
+        "INSERT INTO {}(txid, vin, prev_txid, prev_vout, script_sig, sequence, witness) \
+        VALUES(:txid, :vin, :prev_txid, :prev_vout, :script_sig, :sequence, :witness)",
+        TXINS_TABLE_NAME,
+    ))?;
+    for (vin, txin) in tx.input.iter().enumerate() {
+        statement.execute(named_params! {
+            ":txid": Impl(txid),
+            ":vin": vin as u32,
+            ":prev_txid": Impl(txin.previous_output.txid),
+            ":prev_vout": txin.previous_output.vout,
+            ":script_sig": Impl(txin.script_sig.clone()),
+            ":sequence": txin.sequence.0,
+            ":witness": Impl(txin.witness.clone()),
+        })?;
+    }
+
+    let mut statement = db_tx.prepare_cached(&format!(
+        "INSERT INTO {}(txid, vout, value, script) VALUES(:txid, :vout, :value, :script)",
+        TXOUTS_TABLE_NAME,
+    ))?;
+    for (vout, txout) in tx.output.iter().enumerate() {
+        statement.execute(named_params! {
+            ":txid": Impl(txid),
+            ":vout": vout as u32,
+            ":value": Impl(txout.value),
+            ":script": Impl(txout.script_pubkey.clone()),
+        })?;
+    }
+    Ok(())
+}
+
+/// Load the transaction of `txid` from the normalized tables.
+///
+/// Returns `None` if the transaction is not stored. Errors if the reconstructed transaction does
+/// not hash to `txid` and the stored wtxid, which indicates that the stored rows are incomplete
+/// or corrupt. The wtxid also covers the witnesses, which the txid doesn't.
+///
+/// Remember to call [`init_sqlite_tables`] beforehand.
+pub fn load_tx_normalized(
+    db_tx: &DbTransaction,
+    txid: Txid,
+) -> rusqlite::Result<Option<Transaction>> {
+    let tx_fields = db_tx
+        .prepare_cached(&format!(
+            "SELECT wtxid, version, lock_time FROM {} WHERE txid=:txid",
+            TXS_TABLE_NAME,
+        ))?
+        .query_row(named_params! { ":txid": Impl(txid) }, |row| {
+            Ok((
+                get_sql::<Impl<Wtxid>>(row, "wtxid")?.into_inner(),
+                get_sql::<i32>(row, "version")?,
+                get_sql::<u32>(row, "lock_time")?,
+            ))
+        })
+        .optional()?;
+    let (wtxid, version, lock_time) = match tx_fields {
+        Some(fields) => fields,
+        None => return Ok(None),
+    };
+
+    let input = db_tx
+        .prepare_cached(&format!(
+            "SELECT prev_txid, prev_vout, script_sig, sequence, witness FROM {} \
+            WHERE txid=:txid ORDER BY vin",
+            TXINS_TABLE_NAME,
+        ))?
+        .query_map(named_params! { ":txid": Impl(txid) }, |row| {
+            Ok(TxIn {
+                previous_output: OutPoint {
+                    txid: get_sql::<Impl<Txid>>(row, "prev_txid")?.into_inner(),
+                    vout: get_sql(row, "prev_vout")?,
+                },
+                script_sig: row
+                    .get::<_, Impl<bitcoin::ScriptBuf>>("script_sig")?
+                    .into_inner(),
+                sequence: Sequence(get_sql(row, "sequence")?),
+                witness: row
+                    .get::<_, Impl<bitcoin::Witness>>("witness")?
+                    .into_inner(),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let output = db_tx
+        .prepare_cached(&format!(
+            "SELECT value, script FROM {} WHERE txid=:txid ORDER BY vout",
+            TXOUTS_TABLE_NAME,
+        ))?
+        .query_map(named_params! { ":txid": Impl(txid) }, |row| {
+            Ok(TxOut {
+                value: get_sql::<Impl<bitcoin::Amount>>(row, "value")?.into_inner(),
+                script_pubkey: row
+                    .get::<_, Impl<bitcoin::ScriptBuf>>("script")?
+                    .into_inner(),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let tx = Transaction {
+        version: transaction::Version(version),
+        lock_time: absolute::LockTime::from_consensus(lock_time),
+        input,
+        output,
+    };
+    let loaded_txid = tx.compute_txid();
+    if loaded_txid != txid {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CORRUPT),
+            Some(format!(
+                "normalized rows of tx {} reconstruct a tx with txid {}",
+                txid, loaded_txid
+            )),
+        ));
+    }
+    let loaded_wtxid = tx.compute_wtxid();
+    if loaded_wtxid != wtxid {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CORRUPT),
+            Some(format!(
+                "normalized rows of tx {} reconstruct a tx with wtxid {} instead of {}",
+                txid, loaded_wtxid, wtxid
+            )),
+        ));
+    }
+    Ok(Some(tx))
+}
+
+/// Returns the txids of stored transactions that spend `outpoint`.
+///
+/// Remember to call [`init_sqlite_tables`] beforehand.
+pub fn spending_txids(db_tx: &DbTransaction, outpoint: OutPoint) -> rusqlite::Result<Vec<Txid>> {
+    let mut statement = db_tx.prepare_cached(&format!(
+        "SELECT txid FROM {} WHERE prev_txid=:prev_txid AND prev_vout=:prev_vout ORDER BY txid",
+        TXINS_TABLE_NAME,
+    ))?;
+    let row_iter = statement.query_map(
+        named_params! {
+            ":prev_txid": Impl(outpoint.txid),
+            ":prev_vout": outpoint.vout,
+        },
+        |row| get_sql::<Impl<Txid>>(row, "txid"),
+    )?;
+    row_iter.map(|row| row.map(Impl::into_inner)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bdk_testenv::{anyhow, hash};
+    use bitcoin::{Amount, ScriptBuf, Witness};
+
+    #[test]
+    fn normalized_tx_round_trips() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        init_sqlite_tables(&db_tx)?;
+
+        let prev_output = OutPoint::new(hash!("prev"), 1);
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::from_consensus(840_000),
+            input: vec![TxIn {
+                previous_output: prev_output,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::from_slice(&[vec![1_u8; 72], vec![2_u8; 33]]),
+            }],
+            output: vec![
+                TxOut {
+                    value: Amount::from_sat(21_000),
+                    script_pubkey: ScriptBuf::new_op_return([0xab_u8; 4]),
+                },
+                TxOut {
+                    value: Amount::ONE_BTC,
+                    script_pubkey: ScriptBuf::new(),
+                },
+            ],
+        };
+        insert_tx_normalized(&db_tx, &tx)?;
+        // inserting again must not duplicate inputs or outputs
+        insert_tx_normalized(&db_tx, &tx)?;
+
+        let txid = tx.compute_txid();
+        assert_eq!(load_tx_normalized(&db_tx, txid)?, Some(tx.clone()));
+        assert_eq!(load_tx_normalized(&db_tx, hash!("missing"))?, None);
+        assert_eq!(spending_txids(&db_tx, prev_output)?, vec![txid]);
+
+        // a changed witness keeps the txid but makes the wtxid mismatch
+        db_tx.execute(
+            &format!("UPDATE {} SET witness = ?1", TXINS_TABLE_NAME),
+            [Impl(Witness::from_slice(&[vec![3_u8; 72]]))],
+        )?;
+        assert!(load_tx_normalized(&db_tx, txid).is_err());
+        insert_tx_normalized(&db_tx, &tx)?;
+        assert_eq!(load_tx_normalized(&db_tx, txid)?, Some(tx));
+
+        // a lost output makes the txid mismatch
+        db_tx.execute(
+            &format!("DELETE FROM {} WHERE vout = 1", TXOUTS_TABLE_NAME),
+            (),
+        )?;
+        assert!(load_tx_normalized(&db_tx, txid).is_err());
+        Ok(())
+    }
+}
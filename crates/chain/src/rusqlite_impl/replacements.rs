@@ -0,0 +1,115 @@
+//! Storage of fee-bump (RBF) relationships between transactions.
+//!
+//! Each row is an edge from a replacement transaction to the transaction it replaced. Following
+//! the edges recovers the replacement history of a payment.
+
+use super::{get_sql, migrate_schema, Impl};
+use crate::collections::BTreeSet;
+use alloc::{format, string::String, vec::Vec};
+use bitcoin::Txid;
+use rusqlite::{named_params, Transaction};
+
+/// Schema name for transaction replacements.
+pub const SCHEMA_NAME: &str = "bdk_tx_replacements";
+/// Name of the table that stores transaction replacements.
+pub const TABLE_NAME: &str = "bdk_tx_replacements";
+
+/// Changes to the stored replacements, as `(replacement, replaced)` txid pairs.
+///
+/// Merging takes the union of the edges.
+pub type ChangeSet = BTreeSet<(Txid, Txid)>;
+
+/// Get v0 of the sqlite schema for transaction replacements.
+pub fn schema_v0() -> String {
+    format!(
+        "CREATE TABLE {} ( \
+        replacement_txid TEXT NOT NULL, \
+        replaced_txid TEXT NOT NULL, \
+        PRIMARY KEY (replacement_txid, replaced_txid) \
+        ) STRICT",
+        TABLE_NAME,
+    )
+}
+
+/// Initialize the sqlite table for transaction replacements.
+pub fn init_sqlite_tables(db_tx: &Transaction) -> rusqlite::Result<()> {
+    migrate_schema(db_tx, SCHEMA_NAME, &[&schema_v0()])
+}
+
+/// Record that `replacement` replaced `replaced`.
+///
+/// Recording the same replacement again has no effect.
+pub fn insert_replacement(
+    db_tx: &Transaction,
+    replacement: Txid,
+    replaced: Txid,
+) -> rusqlite::Result<()> {
+    db_tx
+        .prepare_cached(&format!(
+            "INSERT OR IGNORE INTO {}(replacement_txid, replaced_txid) VALUES(:replacement, :replaced)",
+            TABLE_NAME,
+        ))?
+        .execute(named_params! {
+            ":replacement": Impl(replacement),
+            ":replaced": Impl(replaced),
+        })?;
+    Ok(())
+}
+
+/// Load all `(replacement, replaced)` txid pairs, ordered by replacement and then replaced txid.
+pub fn load_replacements(db_tx: &Transaction) -> rusqlite::Result<Vec<(Txid, Txid)>> {
+    let mut statement = db_tx.prepare(&format!(
+        "SELECT replacement_txid, replaced_txid FROM {} ORDER BY replacement_txid, replaced_txid",
+        TABLE_NAME,
+    ))?;
+    let rows = statement.query_map([], |row| {
+        Ok((
+            get_sql::<Impl<Txid>>(row, "replacement_txid")?,
+            get_sql::<Impl<Txid>>(row, "replaced_txid")?,
+        ))
+    })?;
+    rows.map(|row| row.map(|(Impl(replacement), Impl(replaced))| (replacement, replaced)))
+        .collect()
+}
+
+/// Load all replacements as a [`ChangeSet`].
+pub fn from_sqlite(db_tx: &Transaction) -> rusqlite::Result<ChangeSet> {
+    Ok(load_replacements(db_tx)?.into_iter().collect())
+}
+
+/// Persist all replacements of `changeset`.
+pub fn persist_to_sqlite(db_tx: &Transaction, changeset: &ChangeSet) -> rusqlite::Result<()> {
+    for &(replacement, replaced) in changeset {
+        insert_replacement(db_tx, replacement, replaced)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Merge;
+    use alloc::string::ToString;
+    use bdk_testenv::{anyhow, hash};
+
+    #[test]
+    fn replacements_are_a_union_of_edges() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        init_sqlite_tables(&db_tx)?;
+
+        let (tx_a, tx_b, tx_c): (Txid, Txid, Txid) = (hash!("a"), hash!("b"), hash!("c"));
+        let mut changeset = ChangeSet::from([(tx_b, tx_a)]);
+        Merge::merge(&mut changeset, [(tx_c, tx_b), (tx_b, tx_a)].into());
+        persist_to_sqlite(&db_tx, &changeset)?;
+        insert_replacement(&db_tx, tx_c, tx_b)?;
+
+        assert_eq!(from_sqlite(&db_tx)?, changeset);
+        let loaded = load_replacements(&db_tx)?;
+        let mut sorted_by_text = changeset.iter().copied().collect::<Vec<_>>();
+        sorted_by_text
+            .sort_by_key(|(replacement, replaced)| (replacement.to_string(), replaced.to_string()));
+        assert_eq!(loaded, sorted_by_text);
+        Ok(())
+    }
+}
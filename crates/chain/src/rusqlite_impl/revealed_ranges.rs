@@ -0,0 +1,147 @@
+//! Storage of the range of revealed derivation indices of each descriptor.
+//!
+//! Revealed indices are contiguous in practice, so storing them as one `[start, end)` range per
+//! descriptor is much more compact than a row per index.
+
+use super::{get_sql, migrate_schema, Impl};
+use crate::collections::BTreeMap;
+use crate::{DescriptorId, Merge};
+use alloc::{format, string::String};
+use core::ops::Range;
+use rusqlite::{named_params, Transaction};
+
+/// Schema name for revealed ranges.
+pub const SCHEMA_NAME: &str = "bdk_revealed_ranges";
+/// Name of the table that stores revealed ranges.
+pub const TABLE_NAME: &str = "bdk_revealed_ranges";
+
+/// Changes to the stored revealed ranges.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeSet {
+    /// Range of revealed derivation indices of each descriptor.
+    pub ranges: BTreeMap<DescriptorId, Range<u32>>,
+}
+
+impl Merge for ChangeSet {
+    /// Merging widens the range of each descriptor to cover both ranges, so the end only ever
+    /// increases.
+    fn merge(&mut self, other: Self) {
+        for (descriptor_id, range) in other.ranges {
+            self.ranges
+                .entry(descriptor_id)
+                .and_modify(|existing| {
+                    existing.start = existing.start.min(range.start);
+                    existing.end = existing.end.max(range.end);
+                })
+                .or_insert(range);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+/// Get v0 of the sqlite schema for revealed ranges.
+pub fn schema_v0() -> String {
+    format!(
+        "CREATE TABLE {} ( \
+        descriptor_id TEXT PRIMARY KEY NOT NULL, \
+        range_start INTEGER NOT NULL, \
+        range_end INTEGER NOT NULL \
+        ) STRICT",
+        TABLE_NAME,
+    )
+}
+
+/// Initialize the sqlite table for revealed ranges.
+pub fn init_sqlite_tables(db_tx: &Transaction) -> rusqlite::Result<()> {
+    migrate_schema(db_tx, SCHEMA_NAME, &[&schema_v0()])
+}
+
+/// Merge `range` into the revealed range stored for `descriptor_id`.
+pub fn upsert_range(
+    db_tx: &Transaction,
+    descriptor_id: DescriptorId,
+    range: Range<u32>,
+) -> rusqlite::Result<()> {
+    db_tx
+        .prepare_cached(&format!(
+            "INSERT INTO {}(descriptor_id, range_start, range_end) VALUES(:descriptor_id, :start, :end) \
+            ON CONFLICT(descriptor_id) DO UPDATE SET range_start=MIN(range_start, :start), range_end=MAX(range_end, :end)",
+            TABLE_NAME,
+        ))?
+        .execute(named_params! {
+            ":descriptor_id": Impl(descriptor_id),
+            ":start": range.start,
+            ":end": range.end,
+        })?;
+    Ok(())
+}
+
+/// Load the revealed ranges of all descriptors.
+pub fn from_sqlite(db_tx: &Transaction) -> rusqlite::Result<ChangeSet> {
+    let mut statement = db_tx.prepare(&format!(
+        "SELECT descriptor_id, range_start, range_end FROM {}",
+        TABLE_NAME,
+    ))?;
+    let rows = statement.query_map([], |row| {
+        Ok((
+            get_sql::<Impl<DescriptorId>>(row, "descriptor_id")?,
+            get_sql::<u32>(row, "range_start")?,
+            get_sql::<u32>(row, "range_end")?,
+        ))
+    })?;
+    let ranges = rows
+        .map(|row| row.map(|(Impl(descriptor_id), start, end)| (descriptor_id, start..end)))
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(ChangeSet { ranges })
+}
+
+/// Persist `changeset`.
+///
+/// Like [`Merge`], stored ranges are widened rather than replaced.
+pub fn persist_to_sqlite(db_tx: &Transaction, changeset: &ChangeSet) -> rusqlite::Result<()> {
+    for (&descriptor_id, range) in &changeset.ranges {
+        upsert_range(db_tx, descriptor_id, range.clone())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bdk_testenv::{anyhow, hash};
+
+    #[test]
+    fn revealed_ranges_are_widened() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        init_sqlite_tables(&db_tx)?;
+
+        let (external, internal) = (DescriptorId(hash!("ext")), DescriptorId(hash!("int")));
+        let mut merged = ChangeSet::default();
+        for update in [
+            ChangeSet {
+                ranges: [(external, 0..10), (internal, 0..2)].into(),
+            },
+            ChangeSet {
+                ranges: [(external, 5..8)].into(),
+            },
+            ChangeSet {
+                ranges: [(internal, 1..5)].into(),
+            },
+        ] {
+            persist_to_sqlite(&db_tx, &update)?;
+            merged.merge(update);
+        }
+        assert_eq!(
+            merged,
+            ChangeSet {
+                ranges: [(external, 0..10), (internal, 0..5)].into(),
+            }
+        );
+        assert_eq!(from_sqlite(&db_tx)?, merged);
+        Ok(())
+    }
+}
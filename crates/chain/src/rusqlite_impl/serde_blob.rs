@@ -0,0 +1,279 @@
+//! Whole-changeset rows in a serialization format chosen at compile time.
+//!
+//! [`SerdeBlob`] generalizes [`JsonBlob`](super::JsonBlob) over a [`SerdeFormat`], so that JSON
+//! (readable with the `sqlite3` shell) and compact binary formats can be used by the same code.
+//! The JSON row helpers of the parent module (such as [`load_merged_row`](super::load_merged_row))
+//! are the [`Json`] instances of the row functions below.
+
+use super::{
+    check_identifiers, migrate_schema, to_sql_error, validate_identifier, InvalidIdentifier,
+};
+use crate::Merge;
+#[cfg(any(feature = "json", feature = "bincode"))]
+use alloc::boxed::Box;
+use alloc::{format, string::String};
+use core::marker::PhantomData;
+#[cfg(any(feature = "json", feature = "bincode"))]
+use rusqlite::types::Type;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{named_params, OptionalExtension, Transaction};
+
+/// A serialization format for the changesets stored by [`SerdeBlob`].
+///
+/// The format is not recorded in the database, and a table always holds a single format.
+/// Switching the format of an existing store is a migration that has to be done by hand, as
+/// persisting in the new format to the old table either fails (the column type is `STRICT`) or
+/// leaves a row that the old format cannot read. To switch, load the changeset with the old
+/// format, persist it to a new table with the new format (within the same transaction) and drop
+/// the old table and its entry in the schemas table.
+pub trait SerdeFormat {
+    /// Type of the column that holds serialized changesets.
+    const COLUMN_TYPE: &'static str;
+
+    /// Serialize `value` into a column value.
+    fn serialize<T: serde::Serialize>(value: &T) -> rusqlite::Result<Value>;
+
+    /// Deserialize a column value produced by [`Self::serialize`].
+    fn deserialize<T: serde::de::DeserializeOwned>(value: ValueRef<'_>) -> rusqlite::Result<T>;
+}
+
+/// Changesets serialized as JSON text.
+///
+/// Rows are identical to those of [`JsonBlob`](super::JsonBlob), so a table can be read with
+/// either.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl SerdeFormat for Json {
+    const COLUMN_TYPE: &'static str = "TEXT";
+
+    fn serialize<T: serde::Serialize>(value: &T) -> rusqlite::Result<Value> {
+        serde_json::to_string(value)
+            .map(Value::Text)
+            .map_err(to_sql_error)
+    }
+
+    fn deserialize<T: serde::de::DeserializeOwned>(value: ValueRef<'_>) -> rusqlite::Result<T> {
+        let json = value.as_str().map_err(|err| {
+            rusqlite::Error::FromSqlConversionFailure(0, value.data_type(), Box::new(err))
+        })?;
+        serde_json::from_str(json)
+            .map_err(|err| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(err)))
+    }
+}
+
+/// Changesets serialized with [`bincode`] (using its default options).
+#[cfg(feature = "bincode")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl SerdeFormat for Bincode {
+    const COLUMN_TYPE: &'static str = "BLOB";
+
+    fn serialize<T: serde::Serialize>(value: &T) -> rusqlite::Result<Value> {
+        use bincode::Options;
+        bincode::DefaultOptions::new()
+            .serialize(value)
+            .map(Value::Blob)
+            .map_err(to_sql_error)
+    }
+
+    fn deserialize<T: serde::de::DeserializeOwned>(value: ValueRef<'_>) -> rusqlite::Result<T> {
+        use bincode::Options;
+        let bytes = value.as_blob().map_err(|err| {
+            rusqlite::Error::FromSqlConversionFailure(0, value.data_type(), Box::new(err))
+        })?;
+        bincode::DefaultOptions::new()
+            .deserialize(bytes)
+            .map_err(|err| rusqlite::Error::FromSqlConversionFailure(0, Type::Blob, err))
+    }
+}
+
+/// Row id that [`SerdeBlob`] stores its changeset under.
+pub(super) const CHANGESET_ROW_ID: &str = "changeset";
+
+/// Get v0 of the schema for a table of changesets serialized with `F`, keyed by a text id.
+pub(super) fn table_schema<F: SerdeFormat>(table_name: &str) -> Result<String, InvalidIdentifier> {
+    validate_identifier(table_name)?;
+    Ok(format!(
+        "CREATE TABLE {} ( \
+        id TEXT PRIMARY KEY NOT NULL, \
+        changeset {} NOT NULL \
+        ) STRICT",
+        table_name,
+        F::COLUMN_TYPE,
+    ))
+}
+
+/// Load the changeset stored under `id` in `table_name`, or `None` if there is no such row.
+pub(super) fn load_row<CS, F>(
+    db_tx: &Transaction,
+    table_name: &str,
+    id: &str,
+) -> rusqlite::Result<Option<CS>>
+where
+    CS: serde::de::DeserializeOwned,
+    F: SerdeFormat,
+{
+    check_identifiers(&[table_name])?;
+    db_tx
+        .query_row(
+            &format!("SELECT changeset FROM {} WHERE id=:id", table_name),
+            named_params! { ":id": id },
+            |row| F::deserialize::<CS>(row.get_ref("changeset")?),
+        )
+        .optional()
+}
+
+/// Merge `incoming` into the changeset stored under `id` in `table_name`.
+pub(super) fn persist_row<CS, F>(
+    db_tx: &Transaction,
+    table_name: &str,
+    id: &str,
+    incoming: &CS,
+) -> rusqlite::Result<()>
+where
+    CS: Merge + Clone + serde::Serialize + serde::de::DeserializeOwned,
+    F: SerdeFormat,
+{
+    let mut changeset = load_row::<CS, F>(db_tx, table_name, id)?.unwrap_or_default();
+    changeset.merge(incoming.clone());
+    db_tx.execute(
+        &format!(
+            "REPLACE INTO {}(id, changeset) VALUES(:id, :changeset)",
+            table_name
+        ),
+        named_params! { ":id": id, ":changeset": F::serialize(&changeset)? },
+    )?;
+    Ok(())
+}
+
+/// A changeset that is persisted as a single row serialized with `F`, merging on write.
+///
+/// Like [`JsonBlob`](super::JsonBlob), each `table_name` holds one changeset and doubles as its
+/// schema name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SerdeBlob<CS, F> {
+    /// The changeset.
+    pub changeset: CS,
+    format: PhantomData<F>,
+}
+
+impl<CS, F> SerdeBlob<CS, F> {
+    /// Wrap `changeset`.
+    pub fn new(changeset: CS) -> Self {
+        Self {
+            changeset,
+            format: PhantomData,
+        }
+    }
+
+    /// Get the inner changeset.
+    pub fn into_inner(self) -> CS {
+        self.changeset
+    }
+}
+
+impl<CS, F> SerdeBlob<CS, F>
+where
+    CS: Default + Merge + Clone + serde::Serialize + serde::de::DeserializeOwned,
+    F: SerdeFormat,
+{
+    /// Get v0 of the schema of the table named `table_name`.
+    ///
+    /// Errors if `table_name` is not a valid identifier.
+    pub fn schema_v0(table_name: &str) -> Result<String, InvalidIdentifier> {
+        table_schema::<F>(table_name)
+    }
+
+    /// Initialize the sqlite table named `table_name`.
+    pub fn init_sqlite_tables(db_tx: &Transaction, table_name: &str) -> rusqlite::Result<()> {
+        let schema = Self::schema_v0(table_name).map_err(to_sql_error)?;
+        migrate_schema(db_tx, table_name, &[&schema])
+    }
+
+    /// Load the changeset from `table_name`, or the default changeset if none was persisted.
+    pub fn from_sqlite(db_tx: &Transaction, table_name: &str) -> rusqlite::Result<Self> {
+        let changeset = load_row::<CS, F>(db_tx, table_name, CHANGESET_ROW_ID)?;
+        Ok(Self::new(changeset.unwrap_or_default()))
+    }
+
+    /// Merge the changeset into the one stored in `table_name`.
+    pub fn persist_to_sqlite(&self, db_tx: &Transaction, table_name: &str) -> rusqlite::Result<()> {
+        Self::persist_changeset(db_tx, table_name, &self.changeset)
+    }
+
+    /// [`Self::persist_to_sqlite`] for a borrowed changeset, for wrappers such as
+    /// [`JsonBlob`](super::JsonBlob).
+    pub(super) fn persist_changeset(
+        db_tx: &Transaction,
+        table_name: &str,
+        changeset: &CS,
+    ) -> rusqlite::Result<()> {
+        if changeset.is_empty() {
+            return Ok(());
+        }
+        persist_row::<CS, F>(db_tx, table_name, CHANGESET_ROW_ID, changeset)
+    }
+}
+
+#[cfg(all(test, any(feature = "json", feature = "bincode")))]
+mod test {
+    use super::*;
+    #[cfg(feature = "json")]
+    use crate::rusqlite_impl::JsonBlob;
+    use crate::{keychain_txout, DescriptorId};
+    use bdk_testenv::{anyhow, hash};
+
+    fn round_trip<F: SerdeFormat>(table_name: &str) -> anyhow::Result<()> {
+        type ChangeSet = keychain_txout::ChangeSet;
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        SerdeBlob::<ChangeSet, F>::init_sqlite_tables(&db_tx, table_name)?;
+
+        let first = ChangeSet {
+            last_revealed: [(DescriptorId(hash!("a")), 3)].into(),
+        };
+        let second = ChangeSet {
+            last_revealed: [(DescriptorId(hash!("b")), 1)].into(),
+        };
+        SerdeBlob::<_, F>::new(first.clone()).persist_to_sqlite(&db_tx, table_name)?;
+        SerdeBlob::<_, F>::new(second.clone()).persist_to_sqlite(&db_tx, table_name)?;
+
+        let mut expected = first;
+        expected.merge(second);
+        assert_eq!(
+            SerdeBlob::<ChangeSet, F>::from_sqlite(&db_tx, table_name)?.into_inner(),
+            expected
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_rows_are_compatible_with_json_blob() -> anyhow::Result<()> {
+        round_trip::<Json>("test_serde_blob_json")?;
+
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        let changeset = keychain_txout::ChangeSet {
+            last_revealed: [(DescriptorId(hash!("a")), 3)].into(),
+        };
+        JsonBlob::<keychain_txout::ChangeSet>::init_sqlite_tables(&db_tx, "test")?;
+        SerdeBlob::<_, Json>::new(changeset.clone()).persist_to_sqlite(&db_tx, "test")?;
+        assert_eq!(
+            JsonBlob::<keychain_txout::ChangeSet>::from_sqlite(&db_tx, "test")?.0,
+            changeset
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_rows_round_trip() -> anyhow::Result<()> {
+        round_trip::<Bincode>("test_serde_blob_bincode")
+    }
+}
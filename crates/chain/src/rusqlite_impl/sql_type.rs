@@ -0,0 +1,131 @@
+//! Runtime dispatch over the [`Impl`] conversions.
+//!
+//! The set of types with [`FromSql`] implementations is fixed at compile time. [`SqlType`] lists
+//! them so that dynamic tools (e.g. exporters) can decode a column of any supported type into a
+//! [`SqlValue`].
+
+use super::Impl;
+#[cfg(feature = "miniscript")]
+use alloc::boxed::Box;
+use rusqlite::types::{FromSql, FromSqlResult, ValueRef};
+
+macro_rules! sql_types {
+    (@value $ty:ty) => { $ty };
+    (@value $ty:ty, $wrapper:ident) => { $wrapper<$ty> };
+    ($($(#[$attr:meta])* $variant:ident($ty:ty) $($wrapper:ident)? => $name:literal,)*) => {
+        /// A type that can be read from a column through its [`Impl`] conversion.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum SqlType {
+            $(
+                $(#[$attr])*
+                #[doc = concat!("[`", stringify!($ty), "`]")]
+                $variant,
+            )*
+        }
+
+        /// A value of one of the [`SqlType`]s.
+        ///
+        /// Large values are boxed to keep the size of the enum down.
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum SqlValue {
+            $(
+                $(#[$attr])*
+                #[doc = concat!("[`", stringify!($ty), "`]")]
+                $variant(sql_types!(@value $ty $(, $wrapper)?)),
+            )*
+        }
+
+        impl SqlType {
+            /// All supported types.
+            pub const ALL: &'static [SqlType] = &[$($(#[$attr])* SqlType::$variant,)*];
+
+            /// Name of the type, as used in the `Impl<T>` conversion.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $($(#[$attr])* SqlType::$variant => $name,)*
+                }
+            }
+
+            /// Decode `value` as this type.
+            pub fn decode(&self, value: ValueRef<'_>) -> FromSqlResult<SqlValue> {
+                Ok(match self {
+                    $(
+                        $(#[$attr])*
+                        SqlType::$variant => {
+                            SqlValue::$variant(Impl::<$ty>::column_result(value)?.0.into())
+                        }
+                    )*
+                })
+            }
+        }
+
+        impl SqlValue {
+            /// The type of the value.
+            pub fn sql_type(&self) -> SqlType {
+                match self {
+                    $($(#[$attr])* SqlValue::$variant(_) => SqlType::$variant,)*
+                }
+            }
+        }
+    };
+}
+
+sql_types! {
+    Txid(bitcoin::Txid) => "Txid",
+    Wtxid(bitcoin::Wtxid) => "Wtxid",
+    BlockHash(bitcoin::BlockHash) => "BlockHash",
+    #[cfg(feature = "miniscript")]
+    DescriptorId(crate::DescriptorId) => "DescriptorId",
+    Transaction(bitcoin::Transaction) => "Transaction",
+    Witness(bitcoin::Witness) => "Witness",
+    Header(bitcoin::block::Header) => "Header",
+    Block(bitcoin::Block) => "Block",
+    TapNodeHash(bitcoin::taproot::TapNodeHash) => "TapNodeHash",
+    ScriptHash(bitcoin::ScriptHash) => "ScriptHash",
+    WScriptHash(bitcoin::WScriptHash) => "WScriptHash",
+    ControlBlock(bitcoin::taproot::ControlBlock) => "ControlBlock",
+    CompressedPublicKey(bitcoin::CompressedPublicKey) => "CompressedPublicKey",
+    Sequence(bitcoin::Sequence) => "Sequence",
+    TxVersion(bitcoin::transaction::Version) => "TxVersion",
+    NonZeroU32(core::num::NonZeroU32) => "NonZeroU32",
+    ScriptBuf(bitcoin::ScriptBuf) => "ScriptBuf",
+    Amount(bitcoin::Amount) => "Amount",
+    #[cfg(feature = "miniscript")]
+    Descriptor(miniscript::Descriptor<miniscript::DescriptorPublicKey>) Box => "Descriptor",
+    Network(bitcoin::Network) => "Network",
+    DerivationPath(bitcoin::bip32::DerivationPath) => "DerivationPath",
+    #[cfg(feature = "uuid")]
+    Uuid(uuid::Uuid) => "Uuid",
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bdk_testenv::{anyhow, hash};
+
+    #[test]
+    fn sql_type_decodes_into_tagged_value() -> anyhow::Result<()> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let txid: bitcoin::Txid = hash!("txid");
+        let amount = bitcoin::Amount::from_sat(21_000);
+
+        let values = conn.query_row("SELECT ?1, ?2", (Impl(txid), Impl(amount)), |row| {
+            Ok((
+                SqlType::Txid.decode(row.get_ref(0)?)?,
+                SqlType::Amount.decode(row.get_ref(1)?)?,
+            ))
+        })?;
+        assert_eq!(values, (SqlValue::Txid(txid), SqlValue::Amount(amount)));
+        assert_eq!(values.1.sql_type(), SqlType::Amount);
+
+        // a txid is not a valid amount
+        let res = conn.query_row("SELECT ?1", [Impl(txid)], |row| {
+            Ok(SqlType::Amount.decode(row.get_ref(0)?))
+        })?;
+        assert!(res.is_err());
+
+        assert!(SqlType::ALL.contains(&SqlType::Txid));
+        assert_eq!(SqlType::Txid.name(), "Txid");
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+//! Persistence of the mempool-ordering timestamps of unconfirmed transactions.
+//!
+//! Canonicalization orders unconfirmed transactions by when they were last seen in (and evicted
+//! from) the mempool. These helpers store both timestamps in a table keyed by txid.
+//!
+//! Timestamps are unix seconds, except for the `_millis` variants which store unix milliseconds
+//! to order events within the same second. A column must only be written with one of the two, as
+//! the stored integers carry no unit and mixing them is unsupported.
+
+use super::{get_sql, to_sql_error, validate_identifier, Impl, InvalidIdentifier};
+use crate::collections::BTreeMap;
+use alloc::{format, string::String};
+use bitcoin::Txid;
+use core::time::Duration;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{named_params, Transaction};
+
+/// A wrapper that stores a [`Duration`] (e.g. since the unix epoch) as `INTEGER` milliseconds.
+///
+/// Sub-millisecond precision is truncated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SqlMillis(pub Duration);
+
+impl FromSql for SqlMillis {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let millis = value.as_i64()?;
+        u64::try_from(millis)
+            .map(|millis| Self(Duration::from_millis(millis)))
+            .map_err(|_| FromSqlError::OutOfRange(millis))
+    }
+}
+
+impl ToSql for SqlMillis {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(i64::try_from(self.0.as_millis())
+            .map_err(to_sql_error)?
+            .into())
+    }
+}
+
+/// Get v0 of the schema for a table that stores `last_seen` and `last_evicted` timestamps.
+///
+/// Use this with [`upsert_last_seen`], [`upsert_last_evicted`] and their loaders. Errors if
+/// `table_name` is not a valid identifier.
+pub fn timestamps_table_schema(table_name: &str) -> Result<String, InvalidIdentifier> {
+    validate_identifier(table_name)?;
+    Ok(format!(
+        "CREATE TABLE {} ( \
+        txid TEXT PRIMARY KEY NOT NULL, \
+        last_seen INTEGER, \
+        last_evicted INTEGER \
+        ) STRICT",
+        table_name,
+    ))
+}
+
+/// Record that `txid` was seen in the mempool at unix time `last_seen`.
+///
+/// The stored timestamp only ever increases, so an older `last_seen` is ignored.
+pub fn upsert_last_seen(
+    db_tx: &Transaction,
+    table_name: &str,
+    txid: Txid,
+    last_seen: u64,
+) -> rusqlite::Result<()> {
+    upsert_timestamp(db_tx, table_name, "last_seen", txid, last_seen)
+}
+
+/// Record that `txid` was evicted from the mempool at unix time `last_evicted`.
+///
+/// The stored timestamp only ever increases, so an older `last_evicted` is ignored.
+pub fn upsert_last_evicted(
+    db_tx: &Transaction,
+    table_name: &str,
+    txid: Txid,
+    last_evicted: u64,
+) -> rusqlite::Result<()> {
+    upsert_timestamp(db_tx, table_name, "last_evicted", txid, last_evicted)
+}
+
+/// Load all `last_seen` timestamps stored in `table_name`.
+pub fn load_last_seen(
+    db_tx: &Transaction,
+    table_name: &str,
+) -> rusqlite::Result<BTreeMap<Txid, u64>> {
+    load_timestamps(db_tx, table_name, "last_seen")
+}
+
+/// Load all `last_evicted` timestamps stored in `table_name`.
+pub fn load_last_evicted(
+    db_tx: &Transaction,
+    table_name: &str,
+) -> rusqlite::Result<BTreeMap<Txid, u64>> {
+    load_timestamps(db_tx, table_name, "last_evicted")
+}
+
+/// Record that `txid` was seen in the mempool at `last_seen` since the unix epoch, with
+/// millisecond precision.
+///
+/// The stored timestamp only ever increases, so an older `last_seen` is ignored.
+pub fn upsert_last_seen_millis(
+    db_tx: &Transaction,
+    table_name: &str,
+    txid: Txid,
+    last_seen: Duration,
+) -> rusqlite::Result<()> {
+    let millis = u64::try_from(last_seen.as_millis()).map_err(to_sql_error)?;
+    upsert_timestamp(db_tx, table_name, "last_seen", txid, millis)
+}
+
+/// Record that `txid` was evicted from the mempool at `last_evicted` since the unix epoch, with
+/// millisecond precision.
+///
+/// The stored timestamp only ever increases, so an older `last_evicted` is ignored.
+pub fn upsert_last_evicted_millis(
+    db_tx: &Transaction,
+    table_name: &str,
+    txid: Txid,
+    last_evicted: Duration,
+) -> rusqlite::Result<()> {
+    let millis = u64::try_from(last_evicted.as_millis()).map_err(to_sql_error)?;
+    upsert_timestamp(db_tx, table_name, "last_evicted", txid, millis)
+}
+
+/// Load all `last_seen` timestamps stored in `table_name` with millisecond precision.
+pub fn load_last_seen_millis(
+    db_tx: &Transaction,
+    table_name: &str,
+) -> rusqlite::Result<BTreeMap<Txid, Duration>> {
+    Ok(load_timestamps(db_tx, table_name, "last_seen")?
+        .into_iter()
+        .map(|(txid, millis)| (txid, Duration::from_millis(millis)))
+        .collect())
+}
+
+/// Load all `last_evicted` timestamps stored in `table_name` with millisecond precision.
+pub fn load_last_evicted_millis(
+    db_tx: &Transaction,
+    table_name: &str,
+) -> rusqlite::Result<BTreeMap<Txid, Duration>> {
+    Ok(load_timestamps(db_tx, table_name, "last_evicted")?
+        .into_iter()
+        .map(|(txid, millis)| (txid, Duration::from_millis(millis)))
+        .collect())
+}
+
+fn upsert_timestamp(
+    db_tx: &Transaction,
+    table_name: &str,
+    column: &str,
+    txid: Txid,
+    timestamp: u64,
+) -> rusqlite::Result<()> {
+    super::check_identifiers(&[table_name])?;
+    let timestamp = i64::try_from(timestamp).map_err(super::to_sql_error)?;
+    let mut statement = db_tx.prepare_cached(&format!(
+        "INSERT INTO {table}(txid, {column}) VALUES(:txid, :timestamp) ON CONFLICT(txid) DO UPDATE SET {column}=MAX(IFNULL({column}, 0), :timestamp)",
+        table = table_name,
+        column = column,
+    ))?;
+    statement.execute(named_params! {
+        ":txid": Impl(txid),
+        ":timestamp": timestamp,
+    })?;
+    Ok(())
+}
+
+fn load_timestamps(
+    db_tx: &Transaction,
+    table_name: &str,
+    column: &str,
+) -> rusqlite::Result<BTreeMap<Txid, u64>> {
+    super::check_identifiers(&[table_name])?;
+    let mut statement = db_tx.prepare(&format!(
+        "SELECT txid, {column} FROM {table} WHERE {column} IS NOT NULL",
+        table = table_name,
+        column = column,
+    ))?;
+    let rows = statement.query_map([], |row| {
+        Ok((
+            get_sql::<Impl<Txid>>(row, "txid")?,
+            get_sql::<u64>(row, column)?,
+        ))
+    })?;
+    rows.map(|row| row.map(|(Impl(txid), timestamp)| (txid, timestamp)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rusqlite_impl::migrate_schema;
+    use bdk_testenv::{anyhow, hash};
+
+    #[test]
+    fn timestamps_only_increase() -> anyhow::Result<()> {
+        const TABLE_NAME: &str = "test_timestamps";
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        migrate_schema(&db_tx, TABLE_NAME, &[&timestamps_table_schema(TABLE_NAME)?])?;
+
+        let (tx_a, tx_b): (Txid, Txid) = (hash!("a"), hash!("b"));
+        upsert_last_seen(&db_tx, TABLE_NAME, tx_a, 20)?;
+        upsert_last_seen(&db_tx, TABLE_NAME, tx_a, 10)?;
+        upsert_last_seen(&db_tx, TABLE_NAME, tx_b, 5)?;
+        upsert_last_evicted(&db_tx, TABLE_NAME, tx_a, 30)?;
+        upsert_last_evicted(&db_tx, TABLE_NAME, tx_a, 25)?;
+
+        assert_eq!(
+            load_last_seen(&db_tx, TABLE_NAME)?,
+            [(tx_a, 20), (tx_b, 5)].into()
+        );
+        assert_eq!(load_last_evicted(&db_tx, TABLE_NAME)?, [(tx_a, 30)].into());
+        assert!(upsert_last_seen(&db_tx, TABLE_NAME, tx_a, u64::MAX).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn millisecond_timestamps_order_within_a_second() -> anyhow::Result<()> {
+        const TABLE_NAME: &str = "test_timestamps_millis";
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        migrate_schema(&db_tx, TABLE_NAME, &[&timestamps_table_schema(TABLE_NAME)?])?;
+
+        let (tx_a, tx_b): (Txid, Txid) = (hash!("a"), hash!("b"));
+        let second = Duration::from_secs(1_700_000_000);
+        upsert_last_seen_millis(
+            &db_tx,
+            TABLE_NAME,
+            tx_a,
+            second + Duration::from_millis(250),
+        )?;
+        upsert_last_seen_millis(
+            &db_tx,
+            TABLE_NAME,
+            tx_a,
+            second + Duration::from_millis(100),
+        )?;
+        upsert_last_seen_millis(
+            &db_tx,
+            TABLE_NAME,
+            tx_b,
+            second + Duration::from_millis(900),
+        )?;
+        upsert_last_evicted_millis(
+            &db_tx,
+            TABLE_NAME,
+            tx_b,
+            second + Duration::from_micros(1_500),
+        )?;
+
+        assert_eq!(
+            load_last_seen_millis(&db_tx, TABLE_NAME)?,
+            [
+                (tx_a, second + Duration::from_millis(250)),
+                (tx_b, second + Duration::from_millis(900))
+            ]
+            .into()
+        );
+        // sub-millisecond precision is truncated
+        assert_eq!(
+            load_last_evicted_millis(&db_tx, TABLE_NAME)?,
+            [(tx_b, second + Duration::from_millis(1))].into()
+        );
+
+        let SqlMillis(loaded) =
+            db_tx.query_row("SELECT ?1", [SqlMillis(Duration::from_millis(42))], |row| {
+                row.get(0)
+            })?;
+        assert_eq!(loaded, Duration::from_millis(42));
+        assert!(db_tx
+            .query_row("SELECT -1", [], |row| row.get::<_, SqlMillis>(0))
+            .is_err());
+        Ok(())
+    }
+}
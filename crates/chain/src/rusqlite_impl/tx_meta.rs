@@ -0,0 +1,300 @@
+//! Storage of application-defined transaction metadata in typed columns.
+//!
+//! Apps that annotate transactions with structured data (e.g. a category, a counterparty or a
+//! memo) describe it with a [`TxMeta`] type, which is stored as one row per txid with a column per
+//! field. Unlike a JSON blob, the fields can then be queried and indexed with SQL.
+
+use super::{
+    check_identifiers, create_keyed_table, get_sql, migrate_schema, to_sql_error,
+    validate_identifiers, Impl, InvalidIdentifier,
+};
+use crate::collections::BTreeMap;
+use crate::Merge;
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+use bitcoin::Txid;
+use rusqlite::types::ToSql;
+use rusqlite::{OptionalExtension, Row, Transaction};
+
+/// Metadata that is attached to transactions and stored in typed columns keyed by txid.
+///
+/// Every field is optional, and an unset field is stored as `NULL`. [`Merge`] should be
+/// last-write-wins per field: a field that is set in the merged-in value replaces the existing
+/// one, and an unset field leaves it unchanged. Stored rows are updated the same way, so a field
+/// can't be cleared once it is set.
+pub trait TxMeta: Merge {
+    /// Name of the table that stores the metadata, which is also its schema name.
+    const TABLE_NAME: &'static str;
+    /// Name and SQL type of each column (e.g. `("category", "TEXT")`).
+    ///
+    /// Columns are nullable, so the type must not include `NOT NULL`. As the table is `STRICT`,
+    /// the type has to be one of `INTEGER`, `REAL`, `TEXT`, `BLOB` or `ANY`.
+    const COLUMNS: &'static [(&'static str, &'static str)];
+
+    /// Values of the columns, in the order of [`Self::COLUMNS`], with `None` for unset fields.
+    fn column_values(&self) -> Vec<Box<dyn ToSql + '_>>;
+
+    /// Read the columns of `row` with [`get_sql`](super::get_sql), e.g.
+    /// `get_sql::<Option<String>>(row, "category")`.
+    fn from_row(row: &Row) -> rusqlite::Result<Self>;
+}
+
+/// Changes to the stored metadata of type `M`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeSet<M> {
+    /// Metadata of each transaction.
+    pub meta: BTreeMap<Txid, M>,
+}
+
+impl<M> Default for ChangeSet<M> {
+    fn default() -> Self {
+        Self {
+            meta: BTreeMap::new(),
+        }
+    }
+}
+
+impl<M: Merge> Merge for ChangeSet<M> {
+    /// Merging merges the metadata of each transaction with [`Merge`] of `M`.
+    fn merge(&mut self, other: Self) {
+        for (txid, meta) in other.meta {
+            match self.meta.get_mut(&txid) {
+                Some(existing) => existing.merge(meta),
+                None => {
+                    self.meta.insert(txid, meta);
+                }
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.meta.is_empty()
+    }
+}
+
+/// Get v0 of the sqlite schema for metadata of type `M`.
+///
+/// Errors if [`TxMeta::TABLE_NAME`] or a name or type of [`TxMeta::COLUMNS`] is not a valid
+/// identifier.
+pub fn schema_v0<M: TxMeta>() -> Result<String, InvalidIdentifier> {
+    validate_identifiers(M::COLUMNS.iter().flat_map(|(name, ty)| [*name, *ty]))?;
+    let columns = M::COLUMNS
+        .iter()
+        .map(|(name, ty)| format!("{} {}", name, ty))
+        .collect::<Vec<_>>();
+    create_keyed_table(
+        M::TABLE_NAME,
+        "txid",
+        "TEXT",
+        &columns.iter().map(String::as_str).collect::<Vec<_>>(),
+    )
+}
+
+/// Initialize the sqlite table for metadata of type `M`.
+pub fn init_sqlite_tables<M: TxMeta>(db_tx: &Transaction) -> rusqlite::Result<()> {
+    let schema = schema_v0::<M>().map_err(to_sql_error)?;
+    migrate_schema(db_tx, M::TABLE_NAME, &[&schema])
+}
+
+fn check_tx_meta_identifiers<M: TxMeta>() -> rusqlite::Result<()> {
+    check_identifiers(&[M::TABLE_NAME])?;
+    check_identifiers(&M::COLUMNS.iter().map(|(name, _)| *name).collect::<Vec<_>>())
+}
+
+fn column_names<M: TxMeta>() -> String {
+    M::COLUMNS
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Get the metadata of the transaction `txid`, if any.
+pub fn meta_for_tx<M: TxMeta>(db_tx: &Transaction, txid: Txid) -> rusqlite::Result<Option<M>> {
+    check_tx_meta_identifiers::<M>()?;
+    db_tx
+        .prepare_cached(&format!(
+            "SELECT {} FROM {} WHERE txid=?1",
+            column_names::<M>(),
+            M::TABLE_NAME,
+        ))?
+        .query_row([Impl(txid)], |row| M::from_row(row))
+        .optional()
+}
+
+/// Merge `meta` into the stored metadata of the transaction `txid`.
+///
+/// Set fields replace the stored ones, and unset fields leave them unchanged.
+pub fn set_meta_for_tx<M: TxMeta>(
+    db_tx: &Transaction,
+    txid: Txid,
+    meta: &M,
+) -> rusqlite::Result<()> {
+    check_tx_meta_identifiers::<M>()?;
+    let placeholders = (2..M::COLUMNS.len() + 2)
+        .map(|i| format!("?{}", i))
+        .collect::<Vec<_>>();
+    let assignments = M::COLUMNS
+        .iter()
+        .map(|(name, _)| format!("{name}=COALESCE(excluded.{name}, {name})", name = name))
+        .collect::<Vec<_>>();
+    let txid = Impl(txid);
+    let values = meta.column_values();
+    let mut params: Vec<&dyn ToSql> = vec![&txid];
+    params.extend(values.iter().map(|value| &**value));
+    db_tx
+        .prepare_cached(&format!(
+            "INSERT INTO {}(txid, {}) VALUES(?1, {}) ON CONFLICT(txid) DO UPDATE SET {}",
+            M::TABLE_NAME,
+            column_names::<M>(),
+            placeholders.join(", "),
+            assignments.join(", "),
+        ))?
+        .execute(&*params)?;
+    Ok(())
+}
+
+/// Load the metadata of all transactions.
+pub fn from_sqlite<M: TxMeta>(db_tx: &Transaction) -> rusqlite::Result<ChangeSet<M>> {
+    check_tx_meta_identifiers::<M>()?;
+    let mut statement = db_tx.prepare(&format!(
+        "SELECT txid, {} FROM {}",
+        column_names::<M>(),
+        M::TABLE_NAME,
+    ))?;
+    let rows = statement.query_map([], |row| {
+        Ok((get_sql::<Impl<Txid>>(row, "txid")?.0, M::from_row(row)?))
+    })?;
+    Ok(ChangeSet {
+        meta: rows.collect::<rusqlite::Result<_>>()?,
+    })
+}
+
+/// Persist `changeset`, merging it into the stored metadata.
+pub fn persist_to_sqlite<M: TxMeta>(
+    db_tx: &Transaction,
+    changeset: &ChangeSet<M>,
+) -> rusqlite::Result<()> {
+    for (&txid, meta) in &changeset.meta {
+        set_meta_for_tx(db_tx, txid, meta)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::ToString;
+    use bdk_testenv::{anyhow, hash};
+
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    struct Annotation {
+        category: Option<String>,
+        counterparty: Option<String>,
+        fiat_cents: Option<i64>,
+    }
+
+    impl Merge for Annotation {
+        fn merge(&mut self, other: Self) {
+            if other.category.is_some() {
+                self.category = other.category;
+            }
+            if other.counterparty.is_some() {
+                self.counterparty = other.counterparty;
+            }
+            if other.fiat_cents.is_some() {
+                self.fiat_cents = other.fiat_cents;
+            }
+        }
+
+        fn is_empty(&self) -> bool {
+            self == &Self::default()
+        }
+    }
+
+    impl TxMeta for Annotation {
+        const TABLE_NAME: &'static str = "test_annotations";
+        const COLUMNS: &'static [(&'static str, &'static str)] = &[
+            ("category", "TEXT"),
+            ("counterparty", "TEXT"),
+            ("fiat_cents", "INTEGER"),
+        ];
+
+        fn column_values(&self) -> Vec<Box<dyn ToSql + '_>> {
+            vec![
+                Box::new(self.category.as_deref()),
+                Box::new(self.counterparty.as_deref()),
+                Box::new(self.fiat_cents),
+            ]
+        }
+
+        fn from_row(row: &Row) -> rusqlite::Result<Self> {
+            Ok(Self {
+                category: row.get("category")?,
+                counterparty: row.get("counterparty")?,
+                fiat_cents: row.get("fiat_cents")?,
+            })
+        }
+    }
+
+    #[test]
+    fn tx_meta_is_last_write_wins_per_field() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        init_sqlite_tables::<Annotation>(&db_tx)?;
+
+        let (tx_a, tx_b): (Txid, Txid) = (hash!("a"), hash!("b"));
+        let mut merged = ChangeSet {
+            meta: [
+                (
+                    tx_a,
+                    Annotation {
+                        category: Some("rent".to_string()),
+                        fiat_cents: Some(120_000),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    tx_b,
+                    Annotation {
+                        counterparty: Some("alice".to_string()),
+                        ..Default::default()
+                    },
+                ),
+            ]
+            .into(),
+        };
+        persist_to_sqlite(&db_tx, &merged)?;
+        let update = ChangeSet {
+            meta: [(
+                tx_a,
+                Annotation {
+                    counterparty: Some("landlord".to_string()),
+                    fiat_cents: Some(125_000),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+        };
+        persist_to_sqlite(&db_tx, &update)?;
+        merged.merge(update);
+
+        assert_eq!(from_sqlite::<Annotation>(&db_tx)?, merged);
+        assert_eq!(
+            meta_for_tx::<Annotation>(&db_tx, tx_a)?,
+            Some(Annotation {
+                category: Some("rent".to_string()),
+                counterparty: Some("landlord".to_string()),
+                fiat_cents: Some(125_000),
+            })
+        );
+        assert_eq!(meta_for_tx::<Annotation>(&db_tx, hash!("c"))?, None);
+
+        // the fields are plain columns that can be queried directly
+        let rent_txids = db_tx
+            .prepare("SELECT txid FROM test_annotations WHERE category='rent'")?
+            .query_map([], |row| row.get::<_, Impl<Txid>>(0).map(|Impl(txid)| txid))?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(rent_txids, vec![tx_a]);
+        Ok(())
+    }
+}
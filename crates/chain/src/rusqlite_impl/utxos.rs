@@ -0,0 +1,181 @@
+//! Storage of a flat list of outputs and whether they are spent.
+//!
+//! Listing the current UTXOs from this table is a single query, without reconstructing the
+//! transaction graph. It is a view maintained alongside the graph, so it is up to the caller to
+//! record outputs and mark them spent, and to mark them unspent again when the spending
+//! transaction is no longer canonical (e.g. after a reorg or a replacement).
+
+use super::{get_sql, migrate_schema, Impl};
+use crate::collections::BTreeMap;
+use crate::Merge;
+use alloc::{format, string::String};
+use bitcoin::{OutPoint, TxOut, Txid};
+use rusqlite::{named_params, Transaction};
+
+/// Schema name for UTXOs.
+pub const SCHEMA_NAME: &str = "bdk_utxos";
+/// Name of the table that stores UTXOs.
+pub const TABLE_NAME: &str = "bdk_utxos";
+
+/// An output and whether it is spent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Utxo {
+    /// The output.
+    pub txout: TxOut,
+    /// Whether the output is spent.
+    pub is_spent: bool,
+}
+
+/// Changes to the stored UTXOs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeSet {
+    /// Outputs by outpoint.
+    pub utxos: BTreeMap<OutPoint, Utxo>,
+}
+
+impl Merge for ChangeSet {
+    /// Merging is last-write-wins per outpoint, so a later update can mark a spent output unspent
+    /// again.
+    fn merge(&mut self, other: Self) {
+        self.utxos.extend(other.utxos);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.utxos.is_empty()
+    }
+}
+
+/// Get v0 of the sqlite schema for UTXOs.
+pub fn schema_v0() -> String {
+    format!(
+        "CREATE TABLE {} ( \
+        txid TEXT NOT NULL, \
+        vout INTEGER NOT NULL, \
+        value INTEGER NOT NULL, \
+        script BLOB NOT NULL, \
+        is_spent INTEGER NOT NULL, \
+        PRIMARY KEY (txid, vout) \
+        ) STRICT",
+        TABLE_NAME,
+    )
+}
+
+/// Initialize the sqlite table for UTXOs.
+pub fn init_sqlite_tables(db_tx: &Transaction) -> rusqlite::Result<()> {
+    migrate_schema(db_tx, SCHEMA_NAME, &[&schema_v0()])
+}
+
+/// Load all stored outputs, spent or not.
+pub fn from_sqlite(db_tx: &Transaction) -> rusqlite::Result<ChangeSet> {
+    Ok(ChangeSet {
+        utxos: load_utxos(db_tx, false)?,
+    })
+}
+
+/// Load the outputs that are not spent.
+pub fn load_unspent(db_tx: &Transaction) -> rusqlite::Result<BTreeMap<OutPoint, TxOut>> {
+    Ok(load_utxos(db_tx, true)?
+        .into_iter()
+        .map(|(outpoint, utxo)| (outpoint, utxo.txout))
+        .collect())
+}
+
+fn load_utxos(
+    db_tx: &Transaction,
+    unspent_only: bool,
+) -> rusqlite::Result<BTreeMap<OutPoint, Utxo>> {
+    let mut statement = db_tx.prepare(&format!(
+        "SELECT txid, vout, value, script, is_spent FROM {} WHERE NOT (:unspent_only AND is_spent)",
+        TABLE_NAME,
+    ))?;
+    let rows = statement.query_map(named_params! { ":unspent_only": unspent_only }, |row| {
+        Ok((
+            get_sql::<Impl<Txid>>(row, "txid")?,
+            get_sql::<u32>(row, "vout")?,
+            get_sql::<Impl<bitcoin::Amount>>(row, "value")?,
+            get_sql::<Impl<bitcoin::ScriptBuf>>(row, "script")?,
+            get_sql::<bool>(row, "is_spent")?,
+        ))
+    })?;
+    rows.map(|row| {
+        row.map(
+            |(Impl(txid), vout, Impl(value), Impl(script_pubkey), is_spent)| {
+                let txout = TxOut {
+                    value,
+                    script_pubkey,
+                };
+                (OutPoint::new(txid, vout), Utxo { txout, is_spent })
+            },
+        )
+    })
+    .collect()
+}
+
+/// Persist `changeset`.
+///
+/// Like [`Merge`], each output replaces the stored one with the same outpoint.
+pub fn persist_to_sqlite(db_tx: &Transaction, changeset: &ChangeSet) -> rusqlite::Result<()> {
+    let mut statement = db_tx.prepare_cached(&format!(
+        "INSERT INTO {}(txid, vout, value, script, is_spent) \
+        VALUES(:txid, :vout, :value, :script, :is_spent) \
+        ON CONFLICT(txid, vout) DO UPDATE SET value=:value, script=:script, is_spent=:is_spent",
+        TABLE_NAME,
+    ))?;
+    for (outpoint, utxo) in &changeset.utxos {
+        statement.execute(named_params! {
+            ":txid": Impl(outpoint.txid),
+            ":vout": outpoint.vout,
+            ":value": Impl(utxo.txout.value),
+            ":script": Impl(utxo.txout.script_pubkey.clone()),
+            ":is_spent": utxo.is_spent,
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bdk_testenv::{anyhow, hash};
+
+    #[test]
+    fn spent_outputs_can_be_marked_unspent() -> anyhow::Result<()> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let db_tx = conn.transaction()?;
+        init_sqlite_tables(&db_tx)?;
+
+        let txout = TxOut {
+            value: bitcoin::Amount::from_sat(10_000),
+            script_pubkey: bitcoin::ScriptBuf::from_bytes(vec![0x51]),
+        };
+        let (spent, unspent) = (OutPoint::new(hash!("a"), 0), OutPoint::new(hash!("a"), 1));
+        let changeset = |outpoint, is_spent| ChangeSet {
+            utxos: [(
+                outpoint,
+                Utxo {
+                    txout: txout.clone(),
+                    is_spent,
+                },
+            )]
+            .into(),
+        };
+
+        let mut merged = ChangeSet::default();
+        for update in [
+            changeset(spent, false),
+            changeset(unspent, false),
+            changeset(spent, true),
+            changeset(unspent, true),
+            // the spending tx of `unspent` was reorged out
+            changeset(unspent, false),
+        ] {
+            persist_to_sqlite(&db_tx, &update)?;
+            merged.merge(update);
+        }
+        assert!(merged.utxos[&spent].is_spent);
+        assert!(!merged.utxos[&unspent].is_spent);
+        assert_eq!(from_sqlite(&db_tx)?, merged);
+        assert_eq!(load_unspent(&db_tx)?, [(unspent, txout)].into());
+        Ok(())
+    }
+}
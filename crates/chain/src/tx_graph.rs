@@ -95,7 +95,7 @@ use crate::BlockId;
 use crate::CanonicalIter;
 use crate::CanonicalReason;
 use crate::ObservedIn;
-use crate::{Anchor, Balance, ChainOracle, ChainPosition, FullTxOut, Merge};
+use crate::{Anchor, Balance, ChainOracle, ChainPosition, EstimateSize, FullTxOut, Merge};
 use alloc::collections::vec_deque::VecDeque;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -1212,6 +1212,19 @@ impl<A: Ord> Merge for ChangeSet<A> {
     }
 }
 
+impl<A> EstimateSize for ChangeSet<A> {
+    fn estimated_size(&self) -> usize {
+        self.txs.iter().map(|tx| tx.total_size()).sum::<usize>()
+            + self
+                .txouts
+                .values()
+                .map(|txout| core::mem::size_of::<OutPoint>() + txout.size())
+                .sum::<usize>()
+            + self.anchors.estimated_size()
+            + self.last_seen.estimated_size()
+    }
+}
+
 impl<A: Ord> ChangeSet<A> {
     /// Transform the [`ChangeSet`] to have [`Anchor`]s of another type.
     ///
@@ -7,7 +7,7 @@ use bdk_chain::{
     local_chain::LocalChain,
     tx_graph::{self, CalculateFeeError},
     tx_graph::{ChangeSet, TxGraph},
-    Anchor, ChainOracle, ChainPosition, Merge,
+    Anchor, ChainOracle, ChainPosition, EstimateSize, Merge,
 };
 use bdk_testenv::{block_id, hash, utils::new_tx};
 use bitcoin::{
@@ -1043,6 +1043,27 @@ fn test_changeset_last_seen_merge() {
     }
 }
 
+/// Ensure that [`EstimateSize::estimated_size`] grows as the changeset accumulates data.
+#[test]
+fn test_changeset_estimated_size_grows_with_merges() {
+    let tx = Arc::new(new_tx(0));
+    let mut changeset = ChangeSet::<BlockId>::default();
+    assert_eq!(changeset.estimated_size(), 0);
+
+    changeset.merge(ChangeSet {
+        txs: [tx.clone()].into(),
+        ..Default::default()
+    });
+    assert_eq!(changeset.estimated_size(), tx.total_size());
+
+    changeset.merge(ChangeSet {
+        anchors: [(block_id!(1, "A"), tx.compute_txid())].into(),
+        last_seen: [(tx.compute_txid(), 1)].into(),
+        ..Default::default()
+    });
+    assert!(changeset.estimated_size() > tx.total_size());
+}
+
 #[test]
 fn transactions_inserted_into_tx_graph_are_not_canonical_until_they_have_an_anchor_in_best_chain() {
     let txs = vec![new_tx(0), new_tx(1)];
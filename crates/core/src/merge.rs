@@ -19,6 +19,34 @@ pub trait Merge: Default {
     }
 }
 
+/// Companion trait of [`Merge`] that estimates how many bytes a value occupies.
+///
+/// This is a cheap heuristic for buffering layers that need to decide when to flush the
+/// changesets they have been merging. It is not meant to be exact and does not need to match any
+/// particular serialization.
+pub trait EstimateSize {
+    /// Returns the estimated size of `self` in bytes.
+    fn estimated_size(&self) -> usize;
+}
+
+impl<K, V> EstimateSize for BTreeMap<K, V> {
+    fn estimated_size(&self) -> usize {
+        self.len() * core::mem::size_of::<(K, V)>()
+    }
+}
+
+impl<T> EstimateSize for BTreeSet<T> {
+    fn estimated_size(&self) -> usize {
+        self.len() * core::mem::size_of::<T>()
+    }
+}
+
+impl<T> EstimateSize for Vec<T> {
+    fn estimated_size(&self) -> usize {
+        self.len() * core::mem::size_of::<T>()
+    }
+}
+
 impl<K: Ord, V> Merge for BTreeMap<K, V> {
     fn merge(&mut self, other: Self) {
         // We use `extend` instead of `BTreeMap::append` due to performance issues with `append`.
@@ -68,6 +96,16 @@ macro_rules! impl_merge_for_tuple {
     }
 }
 
+macro_rules! impl_estimate_size_for_tuple {
+    ($($a:ident $b:tt)*) => {
+        impl<$($a),*> EstimateSize for ($($a,)*) where $($a: EstimateSize),* {
+            fn estimated_size(&self) -> usize {
+                $(EstimateSize::estimated_size(&self.$b) + )* 0
+            }
+        }
+    }
+}
+
 impl_merge_for_tuple!();
 impl_merge_for_tuple!(T0 0);
 impl_merge_for_tuple!(T0 0 T1 1);
@@ -80,3 +118,16 @@ impl_merge_for_tuple!(T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6 T7 7);
 impl_merge_for_tuple!(T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6 T7 7 T8 8);
 impl_merge_for_tuple!(T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6 T7 7 T8 8 T9 9);
 impl_merge_for_tuple!(T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6 T7 7 T8 8 T9 9 T10 10);
+
+impl_estimate_size_for_tuple!();
+impl_estimate_size_for_tuple!(T0 0);
+impl_estimate_size_for_tuple!(T0 0 T1 1);
+impl_estimate_size_for_tuple!(T0 0 T1 1 T2 2);
+impl_estimate_size_for_tuple!(T0 0 T1 1 T2 2 T3 3);
+impl_estimate_size_for_tuple!(T0 0 T1 1 T2 2 T3 3 T4 4);
+impl_estimate_size_for_tuple!(T0 0 T1 1 T2 2 T3 3 T4 4 T5 5);
+impl_estimate_size_for_tuple!(T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6);
+impl_estimate_size_for_tuple!(T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6 T7 7);
+impl_estimate_size_for_tuple!(T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6 T7 7 T8 8);
+impl_estimate_size_for_tuple!(T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6 T7 7 T8 8 T9 9);
+impl_estimate_size_for_tuple!(T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6 T7 7 T8 8 T9 9 T10 10);
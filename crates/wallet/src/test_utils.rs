@@ -345,3 +345,149 @@ pub fn insert_seen_at(wallet: &mut Wallet, txid: Txid, seen_at: u64) {
         })
         .unwrap();
 }
+
+/// Assert that persisting `a` and then `b` to a sqlite database loads the same [`ChangeSet`] as
+/// persisting `a` merged with `b` in one go.
+///
+/// This holds if [`Merge`] and the sqlite writes of every component agree, so a failure points
+/// to one of them diverging from the other.
+///
+/// [`ChangeSet`]: crate::ChangeSet
+/// [`Merge`]: bdk_chain::Merge
+#[cfg(feature = "rusqlite")]
+pub fn assert_merge_persist_equivalence(a: &crate::ChangeSet, b: &crate::ChangeSet) {
+    use bdk_chain::{rusqlite::Connection, Merge};
+
+    let load = |changesets: &[&crate::ChangeSet]| {
+        let mut conn = Connection::open_in_memory().expect("must open database");
+        let db_tx = conn.transaction().expect("must start transaction");
+        crate::ChangeSet::init_sqlite_tables(&db_tx).expect("must init tables");
+        for changeset in changesets {
+            changeset.persist_to_sqlite(&db_tx).expect("must persist");
+        }
+        crate::ChangeSet::from_sqlite(&db_tx).expect("must load")
+    };
+
+    let mut merged = a.clone();
+    merged.merge(b.clone());
+    assert_eq!(
+        load(&[a, b]),
+        load(&[&merged]),
+        "persisting sequentially must load the same changeset as persisting the merged changeset"
+    );
+}
+
+/// Run every version of `versioned_scripts` in order against a fresh in-memory database, then run
+/// them a second time to check that migrating an up-to-date schema is a no-op.
+///
+/// Use this in a test suite to catch a migration script that references a table or column that
+/// an earlier version does not create.
+#[cfg(feature = "rusqlite")]
+pub fn run_all_migrations(
+    schema_name: &str,
+    versioned_scripts: &[&str],
+) -> bdk_chain::rusqlite::Result<()> {
+    use bdk_chain::rusqlite::Connection;
+    use bdk_chain::rusqlite_impl::migrate_schema;
+
+    let mut conn = Connection::open_in_memory()?;
+    for _ in 0..2 {
+        let db_tx = conn.transaction()?;
+        migrate_schema(&db_tx, schema_name, versioned_scripts)?;
+        db_tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Assert that `changeset` loads back unchanged after persisting it to a fresh sqlite database.
+///
+/// On mismatch, the panic message lists each component that differs with its expected and
+/// loaded values.
+#[cfg(feature = "rusqlite")]
+pub fn assert_sqlite_round_trip(changeset: &crate::ChangeSet) {
+    use alloc::{format, vec::Vec};
+    use bdk_chain::rusqlite::Connection;
+
+    let mut conn = Connection::open_in_memory().expect("must open database");
+    let db_tx = conn.transaction().expect("must start transaction");
+    crate::ChangeSet::init_sqlite_tables(&db_tx).expect("must init tables");
+    changeset.persist_to_sqlite(&db_tx).expect("must persist");
+    let loaded = crate::ChangeSet::from_sqlite(&db_tx).expect("must load");
+
+    let mut diffs = Vec::new();
+    macro_rules! diff_field {
+        ($field:ident) => {
+            if changeset.$field != loaded.$field {
+                diffs.push(format!(
+                    "{}:\n  expected: {:?}\n  loaded: {:?}",
+                    stringify!($field),
+                    changeset.$field,
+                    loaded.$field
+                ));
+            }
+        };
+    }
+    diff_field!(descriptor);
+    diff_field!(change_descriptor);
+    diff_field!(network);
+    diff_field!(local_chain);
+    diff_field!(tx_graph);
+    diff_field!(indexer);
+    assert!(
+        diffs.is_empty(),
+        "changeset changed in sqlite round trip\n{}",
+        diffs.join("\n")
+    );
+}
+
+/// Assert that migrating a database from every version of `versioned_scripts` ends with the same
+/// schema as migrating a fresh database.
+///
+/// For each start version, a fresh in-memory database is first migrated to that version alone,
+/// then to the latest one. The resulting tables, indexes and recorded schema version must equal
+/// those of running all migrations at once. This catches migrations whose outcome depends on the
+/// version they start from, unlike [`run_all_migrations`] which only starts from scratch.
+#[cfg(feature = "rusqlite")]
+pub fn assert_migrations_converge(schema_name: &str, versioned_scripts: &[&str]) {
+    use alloc::{string::String, vec::Vec};
+    use bdk_chain::rusqlite::Connection;
+    use bdk_chain::rusqlite_impl::{migrate_schema, stored_schema_version};
+
+    let migrate = |start_version: usize| {
+        let mut conn = Connection::open_in_memory().expect("must open database");
+        for scripts in [&versioned_scripts[..start_version], versioned_scripts] {
+            let db_tx = conn.transaction().expect("must start transaction");
+            migrate_schema(&db_tx, schema_name, scripts).unwrap_or_else(|err| {
+                panic!("migrating from version {} failed: {}", start_version, err)
+            });
+            db_tx.commit().expect("must commit");
+        }
+        let db_tx = conn.transaction().expect("must start transaction");
+        let version = stored_schema_version(&db_tx, schema_name).expect("must read version");
+        let schema = db_tx
+            .prepare("SELECT type, name, sql FROM sqlite_master ORDER BY type, name")
+            .and_then(|mut statement| {
+                statement
+                    .query_map([], |row| {
+                        Ok((
+                            row.get::<_, String>(0)?,
+                            row.get::<_, String>(1)?,
+                            row.get::<_, Option<String>>(2)?,
+                        ))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .expect("must read schema");
+        (version, schema)
+    };
+
+    let expected = migrate(0);
+    for start_version in 1..=versioned_scripts.len() {
+        assert_eq!(
+            migrate(start_version),
+            expected,
+            "migrating from version {} must end with the same schema as migrating from scratch",
+            start_version
+        );
+    }
+}
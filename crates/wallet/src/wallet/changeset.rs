@@ -1,7 +1,8 @@
 use bdk_chain::{
-    indexed_tx_graph, keychain_txout, local_chain, tx_graph, ConfirmationBlockTime, Merge,
+    indexed_tx_graph, keychain_txout, local_chain, tx_graph, ConfirmationBlockTime, EstimateSize,
+    Merge,
 };
-use miniscript::{Descriptor, DescriptorPublicKey};
+use miniscript::{Descriptor, DescriptorPublicKey, ForEachKey};
 
 type IndexedTxGraphChangeSet =
     indexed_tx_graph::ChangeSet<ConfirmationBlockTime, keychain_txout::ChangeSet>;
@@ -64,10 +65,34 @@ impl Merge for ChangeSet {
     }
 }
 
+impl EstimateSize for ChangeSet {
+    fn estimated_size(&self) -> usize {
+        // count keys rather than render the descriptor, so that estimating doesn't allocate
+        let descriptor_size = |desc: &Option<Descriptor<DescriptorPublicKey>>| {
+            desc.as_ref().map_or(0, |desc| {
+                let mut key_count = 0;
+                desc.for_each_key(|_| {
+                    key_count += 1;
+                    true
+                });
+                core::mem::size_of::<Descriptor<DescriptorPublicKey>>()
+                    + key_count * core::mem::size_of::<DescriptorPublicKey>()
+            })
+        };
+        descriptor_size(&self.descriptor)
+            + descriptor_size(&self.change_descriptor)
+            + self.local_chain.estimated_size()
+            + self.tx_graph.estimated_size()
+            + self.indexer.estimated_size()
+    }
+}
+
 #[cfg(feature = "rusqlite")]
 impl ChangeSet {
     /// Schema name for wallet.
     pub const WALLET_SCHEMA_NAME: &'static str = "bdk_wallet";
+    /// Latest version of the wallet schema, which [`Self::init_sqlite_tables`] migrates to.
+    pub const WALLET_SCHEMA_VERSION: u32 = 0;
     /// Name of table to store wallet descriptors and network.
     pub const WALLET_TABLE_NAME: &'static str = "bdk_wallet";
 
@@ -84,8 +109,69 @@ impl ChangeSet {
         )
     }
 
+    /// Names of all schemas registered by [`init_sqlite_tables`](Self::init_sqlite_tables),
+    /// including those of the wallet's components.
+    pub fn schema_names() -> alloc::vec::Vec<&'static str> {
+        let mut schema_names = alloc::vec![Self::WALLET_SCHEMA_NAME];
+        schema_names.extend(local_chain::ChangeSet::SCHEMA_NAMES);
+        schema_names.extend(tx_graph::ChangeSet::<ConfirmationBlockTime>::SCHEMA_NAMES);
+        schema_names.extend(keychain_txout::ChangeSet::SCHEMA_NAMES);
+        schema_names
+    }
+
+    /// Names of all tables created by [`init_sqlite_tables`](Self::init_sqlite_tables),
+    /// including those of the wallet's components.
+    ///
+    /// Pass these to [`warm_cache`](crate::rusqlite_impl::warm_cache) to preload the wallet.
+    pub fn table_names() -> alloc::vec::Vec<&'static str> {
+        let mut table_names = alloc::vec![Self::WALLET_TABLE_NAME];
+        table_names.extend(local_chain::ChangeSet::TABLE_NAMES);
+        table_names.extend(tx_graph::ChangeSet::<ConfirmationBlockTime>::TABLE_NAMES);
+        table_names.extend(keychain_txout::ChangeSet::TABLE_NAMES);
+        table_names
+    }
+
+    /// Report the stored and latest supported version of every schema of the wallet, as
+    /// `(schema_name, stored_version, latest_version)` tuples.
+    ///
+    /// A stored version greater than the latest version means the database was migrated by a
+    /// newer release, and identifies which component is incompatible. The stored version is
+    /// `None` for schemas that have not been initialized yet.
+    pub fn sqlite_schema_versions(
+        db_tx: &chain::rusqlite::Transaction,
+    ) -> chain::rusqlite::Result<alloc::vec::Vec<(&'static str, Option<u32>, u32)>> {
+        [
+            (Self::WALLET_SCHEMA_NAME, Self::WALLET_SCHEMA_VERSION),
+            (
+                local_chain::ChangeSet::SCHEMA_NAME,
+                local_chain::ChangeSet::SCHEMA_VERSION,
+            ),
+            (
+                tx_graph::ChangeSet::<ConfirmationBlockTime>::SCHEMA_NAME,
+                tx_graph::ChangeSet::<ConfirmationBlockTime>::SCHEMA_VERSION,
+            ),
+            (
+                keychain_txout::ChangeSet::SCHEMA_NAME,
+                keychain_txout::ChangeSet::SCHEMA_VERSION,
+            ),
+        ]
+        .into_iter()
+        .map(|(schema_name, latest_version)| {
+            let stored_version = crate::rusqlite_impl::stored_schema_version(db_tx, schema_name)?;
+            Ok((schema_name, stored_version, latest_version))
+        })
+        .collect()
+    }
+
     /// Initialize sqlite tables for wallet tables.
+    ///
+    /// Migrations only need to run once per database, not once per connection. To keep schema
+    /// changes out of the connections used for normal operation, this can be called (and
+    /// committed) on a separate connection at startup; other connections can then call
+    /// [`from_sqlite`](Self::from_sqlite) directly. This is what the
+    /// [`MigratedConnection`](crate::MigratedConnection) persister does.
     pub fn init_sqlite_tables(db_tx: &chain::rusqlite::Transaction) -> chain::rusqlite::Result<()> {
+        crate::rusqlite_impl::check_unique_schema_names(&Self::schema_names())?;
         crate::rusqlite_impl::migrate_schema(
             db_tx,
             Self::WALLET_SCHEMA_NAME,
@@ -99,8 +185,30 @@ impl ChangeSet {
         Ok(())
     }
 
+    /// Delete all wallet data from the sqlite tables while keeping the tables and their schema
+    /// versions.
+    ///
+    /// This is useful to forget a wallet (or force a full rescan) without re-running migrations.
+    pub fn clear_sqlite_tables(
+        db_tx: &chain::rusqlite::Transaction,
+    ) -> chain::rusqlite::Result<()> {
+        db_tx.execute(&format!("DELETE FROM {}", Self::WALLET_TABLE_NAME), ())?;
+
+        bdk_chain::local_chain::ChangeSet::clear_sqlite_tables(db_tx)?;
+        bdk_chain::tx_graph::ChangeSet::<ConfirmationBlockTime>::clear_sqlite_tables(db_tx)?;
+        bdk_chain::keychain_txout::ChangeSet::clear_sqlite_tables(db_tx)?;
+
+        Ok(())
+    }
+
     /// Recover a [`ChangeSet`] from sqlite database.
+    ///
+    /// This assumes that [`init_sqlite_tables`](Self::init_sqlite_tables) has already migrated
+    /// the database. If it has not, loading fails with a "no such table" (or "no such column")
+    /// error rather than returning partial data. Use
+    /// [`schema_at_least`](crate::rusqlite_impl::schema_at_least) to check beforehand.
     pub fn from_sqlite(db_tx: &chain::rusqlite::Transaction) -> chain::rusqlite::Result<Self> {
+        use crate::rusqlite_impl::get_sql;
         use chain::rusqlite::OptionalExtension;
         use chain::Impl;
 
@@ -113,11 +221,12 @@ impl ChangeSet {
         let row = wallet_statement
             .query_row([], |row| {
                 Ok((
-                    row.get::<_, Option<Impl<Descriptor<DescriptorPublicKey>>>>("descriptor")?,
-                    row.get::<_, Option<Impl<Descriptor<DescriptorPublicKey>>>>(
+                    get_sql::<Option<Impl<Descriptor<DescriptorPublicKey>>>>(row, "descriptor")?,
+                    get_sql::<Option<Impl<Descriptor<DescriptorPublicKey>>>>(
+                        row,
                         "change_descriptor",
                     )?,
-                    row.get::<_, Option<Impl<bitcoin::Network>>>("network")?,
+                    get_sql::<Option<Impl<bitcoin::Network>>>(row, "network")?,
                 ))
             })
             .optional()?;
@@ -135,6 +244,11 @@ impl ChangeSet {
     }
 
     /// Persist [`ChangeSet`] to sqlite database.
+    ///
+    /// This writes every component of the changeset. To only write a single component (and avoid
+    /// touching the tables of the others), call `persist_to_sqlite` on that component directly
+    /// within the same `db_tx`. For example, persisting only `changeset.indexer` is done with
+    /// [`keychain_txout::ChangeSet::persist_to_sqlite`].
     pub fn persist_to_sqlite(
         &self,
         db_tx: &chain::rusqlite::Transaction,
@@ -180,6 +294,24 @@ impl ChangeSet {
         self.indexer.persist_to_sqlite(db_tx)?;
         Ok(())
     }
+
+    /// Persist [`ChangeSet`] and load the resulting aggregate changeset in one transaction.
+    ///
+    /// The transaction takes the write lock up front (`BEGIN IMMEDIATE`), so no other writer can
+    /// interleave between the write and the read. The returned changeset is the complete state of
+    /// the database, e.g. to construct a fresh wallet without a separate reload. Remember to call
+    /// [`init_sqlite_tables`](Self::init_sqlite_tables) beforehand.
+    pub fn persist_and_load_sqlite(
+        &self,
+        conn: &mut chain::rusqlite::Connection,
+    ) -> chain::rusqlite::Result<Self> {
+        let db_tx =
+            conn.transaction_with_behavior(chain::rusqlite::TransactionBehavior::Immediate)?;
+        self.persist_to_sqlite(&db_tx)?;
+        let changeset = Self::from_sqlite(&db_tx)?;
+        db_tx.commit()?;
+        Ok(changeset)
+    }
 }
 
 impl From<local_chain::ChangeSet> for ChangeSet {
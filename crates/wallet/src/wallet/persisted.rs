@@ -286,6 +286,76 @@ impl WalletPersister for bdk_chain::rusqlite::Connection {
     }
 }
 
+/// A sqlite connection that is persisted to without running schema migrations.
+///
+/// The [`WalletPersister`] of a plain [`Connection`](bdk_chain::rusqlite::Connection) migrates the
+/// wallet schemas every time it is initialized. To keep schema changes out of the connections used
+/// for normal operation, run the migrations once at startup with
+/// [`run_migrations`](Self::run_migrations) (e.g. on a separate connection), and wrap the other
+/// connections with [`new_assume_migrated`](Self::new_assume_migrated).
+///
+/// Nothing checks that the migrations did run. If they did not (or ran with an older release),
+/// loading fails with a "no such table" or "no such column" error, and so does persisting, in
+/// which case nothing of the changeset is written.
+#[cfg(feature = "rusqlite")]
+#[derive(Debug)]
+pub struct MigratedConnection(bdk_chain::rusqlite::Connection);
+
+#[cfg(feature = "rusqlite")]
+impl MigratedConnection {
+    /// Migrate the wallet schemas of the database of `conn` to the latest versions.
+    pub fn run_migrations(
+        conn: &mut bdk_chain::rusqlite::Connection,
+    ) -> bdk_chain::rusqlite::Result<()> {
+        let db_tx = conn.transaction()?;
+        ChangeSet::init_sqlite_tables(&db_tx)?;
+        db_tx.commit()
+    }
+
+    /// Wrap `conn`, trusting that [`run_migrations`](Self::run_migrations) already ran on its
+    /// database.
+    pub fn new_assume_migrated(conn: bdk_chain::rusqlite::Connection) -> Self {
+        Self(conn)
+    }
+
+    /// Get the inner connection.
+    pub fn into_inner(self) -> bdk_chain::rusqlite::Connection {
+        self.0
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl Deref for MigratedConnection {
+    type Target = bdk_chain::rusqlite::Connection;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl DerefMut for MigratedConnection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl WalletPersister for MigratedConnection {
+    type Error = bdk_chain::rusqlite::Error;
+
+    fn initialize(persister: &mut Self) -> Result<ChangeSet, Self::Error> {
+        let db_tx = persister.transaction()?;
+        let changeset = ChangeSet::from_sqlite(&db_tx)?;
+        db_tx.commit()?;
+        Ok(changeset)
+    }
+
+    fn persist(persister: &mut Self, changeset: &ChangeSet) -> Result<(), Self::Error> {
+        WalletPersister::persist(&mut persister.0, changeset)
+    }
+}
+
 /// Error for [`bdk_file_store`]'s implementation of [`WalletPersister`].
 #[cfg(feature = "file_store")]
 #[derive(Debug)]
@@ -13,7 +13,7 @@ use bdk_wallet::signer::{SignOptions, SignerError};
 use bdk_wallet::test_utils::*;
 use bdk_wallet::tx_builder::AddForeignUtxoError;
 use bdk_wallet::{AddressInfo, Balance, ChangeSet, Wallet, WalletPersister, WalletTx};
-use bdk_wallet::{KeychainKind, LoadError, LoadMismatch, LoadWithPersistError};
+use bdk_wallet::{KeychainKind, LoadError, LoadMismatch, LoadWithPersistError, MigratedConnection};
 use bitcoin::constants::{ChainHash, COINBASE_MATURITY};
 use bitcoin::hashes::Hash;
 use bitcoin::key::Secp256k1;
@@ -329,6 +329,264 @@ fn single_descriptor_wallet_persist_and_recover() {
     );
 }
 
+#[test]
+fn sqlite_load_only_populates_persisted_components() -> anyhow::Result<()> {
+    use bdk_chain::{keychain_txout, rusqlite, DescriptorExt};
+
+    let mut conn = rusqlite::Connection::open_in_memory()?;
+    let (desc, _) = parse_descriptor(get_test_wpkh());
+    let indexer = keychain_txout::ChangeSet {
+        last_revealed: [(desc.descriptor_id(), 7)].into(),
+    };
+
+    // only the indexer component has data
+    {
+        let db_tx = conn.transaction()?;
+        ChangeSet::init_sqlite_tables(&db_tx)?;
+        indexer.persist_to_sqlite(&db_tx)?;
+        db_tx.commit()?;
+    }
+
+    let changeset = WalletPersister::initialize(&mut conn)?;
+    assert_eq!(
+        changeset,
+        ChangeSet {
+            indexer,
+            ..Default::default()
+        },
+        "empty components must load as default while the non-empty one is kept"
+    );
+    Ok(())
+}
+
+#[test]
+fn sqlite_sequential_persist_matches_merged_persist() {
+    use bdk_chain::{keychain_txout, local_chain, tx_graph, DescriptorExt};
+
+    let (desc, _) = parse_descriptor(get_test_wpkh());
+    let tx = Arc::new(new_tx(0));
+    let txid = tx.compute_txid();
+    let a = ChangeSet {
+        local_chain: local_chain::ChangeSet::from([(0, Some(BlockHash::all_zeros()))]),
+        tx_graph: tx_graph::ChangeSet {
+            txs: [tx].into(),
+            last_seen: [(txid, 200)].into(),
+            ..Default::default()
+        },
+        indexer: keychain_txout::ChangeSet {
+            last_revealed: [(desc.descriptor_id(), 7)].into(),
+        },
+        ..Default::default()
+    };
+    let b = ChangeSet {
+        tx_graph: tx_graph::ChangeSet {
+            last_seen: [(txid, 100)].into(),
+            ..Default::default()
+        },
+        indexer: keychain_txout::ChangeSet {
+            last_revealed: [(desc.descriptor_id(), 3)].into(),
+        },
+        ..Default::default()
+    };
+    assert_merge_persist_equivalence(&a, &b);
+    assert_merge_persist_equivalence(&b, &a);
+}
+
+#[test]
+fn sqlite_persist_and_load_returns_aggregate() -> anyhow::Result<()> {
+    use bdk_chain::{local_chain, rusqlite, Merge};
+
+    let mut conn = rusqlite::Connection::open_in_memory()?;
+    let stored = WalletPersister::initialize(&mut conn)?;
+    let a = ChangeSet {
+        network: Some(Network::Testnet),
+        local_chain: local_chain::ChangeSet::from([(0, Some(BlockHash::all_zeros()))]),
+        ..Default::default()
+    };
+    WalletPersister::persist(&mut conn, &a)?;
+    let b = ChangeSet {
+        local_chain: local_chain::ChangeSet::from([(1, Some(BlockHash::from_byte_array([1; 32])))]),
+        ..Default::default()
+    };
+
+    let loaded = b.persist_and_load_sqlite(&mut conn)?;
+    let mut expected = stored;
+    expected.merge(a);
+    expected.merge(b);
+    assert_eq!(loaded, expected);
+    assert_eq!(WalletPersister::initialize(&mut conn)?, expected);
+    Ok(())
+}
+
+#[test]
+fn migrated_connection_persists_without_migrating() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite;
+    let temp_dir = tempfile::tempdir()?;
+    let db_path = temp_dir.path().join("wallet.db");
+
+    let mut db = MigratedConnection::new_assume_migrated(rusqlite::Connection::open(&db_path)?);
+    assert!(
+        Wallet::load().load_wallet(&mut db).is_err(),
+        "loading must fail before the migrations ran"
+    );
+
+    MigratedConnection::run_migrations(&mut rusqlite::Connection::open(&db_path)?)?;
+    let desc = get_test_tr_single_sig_xprv();
+    let mut wallet = Wallet::create_single(desc)
+        .network(Network::Testnet)
+        .create_wallet(&mut db)
+        .expect("must create wallet");
+    let _ = wallet.reveal_next_address(KeychainKind::External);
+    assert!(wallet.persist(&mut db)?);
+
+    let mut db = MigratedConnection::new_assume_migrated(db.into_inner());
+    let wallet = Wallet::load()
+        .load_wallet(&mut db)
+        .expect("must load wallet")
+        .expect("must have loaded changeset");
+    assert_eq!(wallet.derivation_index(KeychainKind::External), Some(0));
+    Ok(())
+}
+
+#[test]
+fn sqlite_table_names_cover_all_tables() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite;
+
+    let mut conn = rusqlite::Connection::open_in_memory()?;
+    let _ = WalletPersister::initialize(&mut conn)?;
+    let mut tables = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name != 'bdk_schemas'")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    tables.sort();
+    let mut table_names = ChangeSet::table_names();
+    table_names.sort();
+    assert_eq!(tables, table_names);
+
+    bdk_chain::rusqlite_impl::warm_cache(&conn, &table_names)?;
+    Ok(())
+}
+
+#[test]
+fn sqlite_schema_names_cover_all_schemas() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite;
+
+    let mut conn = rusqlite::Connection::open_in_memory()?;
+    let _ = WalletPersister::initialize(&mut conn)?;
+    let mut schemas = conn
+        .prepare("SELECT name FROM bdk_schemas")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    schemas.sort();
+    let mut schema_names = ChangeSet::schema_names();
+    schema_names.sort();
+    assert_eq!(schemas, schema_names);
+    Ok(())
+}
+
+#[test]
+fn sqlite_migrations_run_from_empty() -> anyhow::Result<()> {
+    use bdk_chain::{keychain_txout, local_chain, tx_graph};
+    type TxGraphChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+
+    run_all_migrations(ChangeSet::WALLET_SCHEMA_NAME, &[&ChangeSet::schema_v0()])?;
+    run_all_migrations(
+        local_chain::ChangeSet::SCHEMA_NAME,
+        &[&local_chain::ChangeSet::schema_v0()],
+    )?;
+    run_all_migrations(
+        TxGraphChangeSet::SCHEMA_NAME,
+        &[
+            &TxGraphChangeSet::schema_v0(),
+            &TxGraphChangeSet::schema_v1(),
+        ],
+    )?;
+    run_all_migrations(
+        keychain_txout::ChangeSet::SCHEMA_NAME,
+        &[&keychain_txout::ChangeSet::schema_v0()],
+    )?;
+
+    // a script referencing a table that no earlier version creates must fail
+    assert!(run_all_migrations("test_broken", &["ALTER TABLE missing ADD COLUMN x"]).is_err());
+    Ok(())
+}
+
+#[test]
+fn sqlite_round_trips_funded_wallet_changeset() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let _ = wallet.reveal_next_address(KeychainKind::External);
+    let changeset = wallet
+        .take_staged()
+        .expect("funded wallet must have staged changes");
+    assert_sqlite_round_trip(&changeset);
+}
+
+#[test]
+fn sqlite_schema_versions_identify_newer_component() -> anyhow::Result<()> {
+    use bdk_chain::{rusqlite, tx_graph};
+    type TxGraphChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+
+    let mut conn = rusqlite::Connection::open_in_memory()?;
+    let _ = WalletPersister::initialize(&mut conn)?;
+    // simulate a newer release migrating the tx graph schema
+    conn.execute(
+        "UPDATE bdk_schemas SET version = version + 1 WHERE name = ?1",
+        [TxGraphChangeSet::SCHEMA_NAME],
+    )?;
+
+    let db_tx = conn.transaction()?;
+    let newer = ChangeSet::sqlite_schema_versions(&db_tx)?
+        .into_iter()
+        .filter(|(_, stored, latest)| stored.map_or(false, |stored| stored > *latest))
+        .map(|(schema_name, _, _)| schema_name)
+        .collect::<Vec<_>>();
+    assert_eq!(newer, vec![TxGraphChangeSet::SCHEMA_NAME]);
+    Ok(())
+}
+
+#[test]
+fn sqlite_migrations_converge_from_every_version() {
+    use bdk_chain::tx_graph;
+    type TxGraphChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+    assert_migrations_converge(
+        TxGraphChangeSet::SCHEMA_NAME,
+        &[
+            &TxGraphChangeSet::schema_v0(),
+            &TxGraphChangeSet::schema_v1(),
+        ],
+    );
+    assert_migrations_converge(
+        "test_converge",
+        &[
+            "CREATE TABLE test_a ( id INTEGER PRIMARY KEY )",
+            "ALTER TABLE test_a ADD COLUMN label TEXT",
+            "CREATE INDEX test_a_label ON test_a(label)",
+        ],
+    );
+}
+
+#[test]
+fn sqlite_load_propagates_component_errors() -> anyhow::Result<()> {
+    use bdk_chain::{keychain_txout, rusqlite};
+
+    let mut conn = rusqlite::Connection::open_in_memory()?;
+    let _ = WalletPersister::initialize(&mut conn)?;
+
+    // an unparsable descriptor id in an otherwise empty database must not load as default
+    conn.execute(
+        &format!(
+            "INSERT INTO {}(descriptor_id, last_revealed) VALUES('not a descriptor id', 1)",
+            keychain_txout::ChangeSet::LAST_REVEALED_TABLE_NAME,
+        ),
+        (),
+    )?;
+    assert!(
+        WalletPersister::initialize(&mut conn).is_err(),
+        "load error of a single component must be surfaced"
+    );
+    Ok(())
+}
+
 #[test]
 fn test_error_external_and_internal_are_the_same() {
     // identical descriptors should fail to create wallet